@@ -0,0 +1,96 @@
+use std::fmt;
+use std::io;
+use crate::forest_property::schema_version::SchemaVersion;
+
+/// Errors produced while reading or writing Forest Data XML.
+#[derive(Debug)]
+pub enum ForestDataError {
+    /// The file could not be read or written.
+    Io(io::Error),
+    /// The bytes could not be decoded with the encoding declared in the document.
+    Encoding {
+        encoding: String,
+        position: u64,
+        message: String,
+    },
+    /// The document is not well-formed XML or does not match the Forest Data model.
+    Schema {
+        path: String,
+        position: u64,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The document was written with a schema revision whose layout is not supported.
+    UnsupportedVersion(SchemaVersion),
+}
+
+impl ForestDataError {
+    /// Element path of the failing element, e.g. `RealEstates/RealEstate/Parcels/Parcel[3]/Stands/Stand[17]`.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            ForestDataError::Schema { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Byte position of the error in the (decoded) document.
+    pub fn position(&self) -> Option<u64> {
+        match self {
+            ForestDataError::Io(_) => None,
+            ForestDataError::Encoding { position, .. } => Some(*position),
+            ForestDataError::Schema { position, .. } => Some(*position),
+            ForestDataError::UnsupportedVersion(_) => None,
+        }
+    }
+
+    pub fn is_io(&self) -> bool {
+        matches!(self, ForestDataError::Io(_))
+    }
+
+    pub fn is_encoding(&self) -> bool {
+        matches!(self, ForestDataError::Encoding { .. })
+    }
+
+    pub fn is_schema(&self) -> bool {
+        matches!(self, ForestDataError::Schema { .. })
+    }
+
+    pub fn is_unsupported_version(&self) -> bool {
+        matches!(self, ForestDataError::UnsupportedVersion(_))
+    }
+}
+
+impl fmt::Display for ForestDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForestDataError::Io(err) => write!(f, "I/O error: {}", err),
+            ForestDataError::Encoding { encoding, position, message } => {
+                write!(f, "Encoding error ({}) at byte {}: {}", encoding, position, message)
+            }
+            ForestDataError::Schema { path, position, line, column, message } => write!(
+                f,
+                "Schema error at {} (line {}, column {}, byte {}): {}",
+                path, line, column, position, message
+            ),
+            ForestDataError::UnsupportedVersion(version) => {
+                write!(f, "Schema version {} is not supported, only MV1.7 and older revisions are", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ForestDataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ForestDataError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ForestDataError {
+    fn from(err: io::Error) -> Self {
+        ForestDataError::Io(err)
+    }
+}
@@ -0,0 +1,330 @@
+use std::ops::{Add, AddAssign, Mul};
+use serde::Serialize;
+use super::codes::TreeSpecies;
+use super::forest_property_data::ForestPropertyData;
+use super::stand::Stand;
+use super::tree::Tree;
+use super::tree_stand_data::{SnapshotSelector, TreeStratum};
+
+/// Share of carbon in dry biomass, the IPCC default
+pub const CARBON_FRACTION: f64 = 0.5;
+/// Mass of CO₂ per mass of carbon
+pub const CO2_PER_CARBON: f64 = 44.0 / 12.0;
+// Breast height, m
+const BREAST_HEIGHT: f64 = 1.3;
+
+/// Height term of a biomass equation, h in metres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeightTerm {
+    None,
+    /// b2 h / (h + k2)
+    Ratio { b2: f64, k2: f64 },
+    /// b2 ln(h)
+    Log { b2: f64 },
+}
+
+/// Biomass equation of Repola (2008, 2009) with diameter and height as predictors,
+/// ln(y) = b0 + b1 dₛ / (dₛ + k1) + height term, where dₛ = 2 + 1.25 d is the stump diameter
+/// in cm and y the dry biomass in kg. Half of the variances of the random stand effect and the
+/// residual is added to correct the bias of the logarithmic model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomassEquation {
+    pub b0: f64,
+    pub b1: f64,
+    pub k1: f64,
+    pub height_term: HeightTerm,
+    pub variance: f64,
+}
+
+impl BiomassEquation {
+    /// kg of a tree of `diameter` cm at breast height and `height` m
+    pub fn biomass(&self, diameter: f64, height: f64) -> f64 {
+        let stump_diameter = 2.0 + 1.25 * diameter;
+        let height_term = match self.height_term {
+            HeightTerm::None => 0.0,
+            HeightTerm::Ratio { b2, k2 } => b2 * height / (height + k2),
+            HeightTerm::Log { b2 } => b2 * height.ln(),
+        };
+        (self.b0 + self.b1 * stump_diameter / (stump_diameter + self.k1) + height_term + self.variance / 2.0).exp()
+    }
+}
+
+/// Stem volume function of Laasasenaho (1982), equation 2, v = a d^b1 c^d h^b2 (h - 1.3)^b3
+/// in dm³ with d in cm and h in metres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeEquation {
+    pub a: f64,
+    pub b1: f64,
+    pub c: f64,
+    pub b2: f64,
+    pub b3: f64,
+}
+
+impl VolumeEquation {
+    /// m³ over bark of a tree of `diameter` cm and `height` m, zero for trees below breast height
+    pub fn volume(&self, diameter: f64, height: f64) -> f64 {
+        if diameter <= 0.0 || height <= BREAST_HEIGHT {
+            return 0.0;
+        }
+        self.a * diameter.powf(self.b1) * self.c.powf(diameter) * height.powf(self.b2) * (height - BREAST_HEIGHT).powf(self.b3) / 1000.0
+    }
+}
+
+/// Biomass models of a species group. Above ground biomass (stem, branches and foliage), stump
+/// and roots are given by the equations of model 1 of Repola (2008, 2009), and stem volume by
+/// the volume function of Laasasenaho (1982). Pine's equations are used for the other conifers
+/// except spruces and firs, and birch's for all broadleaves.
+///
+/// - Repola, J. 2008. Biomass equations for birch in Finland. Silva Fennica 42(4): 605–624.
+/// - Repola, J. 2009. Biomass equations for Scots pine and Norway spruce in Finland.
+///   Silva Fennica 43(4): 625–647.
+/// - Laasasenaho, J. 1982. Taper curve and volume functions for pine, spruce and birch.
+///   Communicationes Instituti Forestalis Fenniae 108.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomassModel {
+    pub above_ground: BiomassEquation,
+    pub stump: BiomassEquation,
+    /// Roots of at least 1 cm in diameter
+    pub roots: BiomassEquation,
+    pub stem_volume: VolumeEquation,
+}
+
+const PINE: BiomassModel = BiomassModel {
+    above_ground: BiomassEquation { b0: -3.198, b1: 9.547, k1: 12.0, height_term: HeightTerm::Ratio { b2: 3.241, k2: 20.0 }, variance: 0.009 + 0.010 },
+    stump: BiomassEquation { b0: -6.753, b1: 12.681, k1: 12.0, height_term: HeightTerm::None, variance: 0.010 + 0.044 },
+    roots: BiomassEquation { b0: -5.550, b1: 13.408, k1: 15.0, height_term: HeightTerm::None, variance: 0.079 },
+    stem_volume: VolumeEquation { a: 0.036089, b1: 2.01395, c: 0.99676, b2: 2.07025, b3: -1.07209 },
+};
+
+const SPRUCE: BiomassModel = BiomassModel {
+    above_ground: BiomassEquation { b0: -1.808, b1: 9.482, k1: 20.0, height_term: HeightTerm::Log { b2: 0.469 }, variance: 0.006 + 0.013 },
+    stump: BiomassEquation { b0: -3.964, b1: 11.730, k1: 26.0, height_term: HeightTerm::None, variance: 0.065 + 0.058 },
+    roots: BiomassEquation { b0: -2.294, b1: 10.646, k1: 24.0, height_term: HeightTerm::None, variance: 0.105 + 0.114 },
+    stem_volume: VolumeEquation { a: 0.022927, b1: 1.91505, c: 0.99146, b2: 2.82541, b3: -1.53547 },
+};
+
+const BIRCH: BiomassModel = BiomassModel {
+    above_ground: BiomassEquation { b0: -3.654, b1: 10.582, k1: 12.0, height_term: HeightTerm::Ratio { b2: 3.018, k2: 22.0 }, variance: 0.00068 + 0.00727 },
+    stump: BiomassEquation { b0: -3.574, b1: 11.304, k1: 26.0, height_term: HeightTerm::None, variance: 0.02154 + 0.04542 },
+    roots: BiomassEquation { b0: -3.223, b1: 6.497, k1: 22.0, height_term: HeightTerm::Log { b2: 1.033 }, variance: 0.048 + 0.02677 },
+    stem_volume: VolumeEquation { a: 0.011197, b1: 2.10253, c: 0.98600, b2: 3.98519, b3: -2.65900 },
+};
+
+impl BiomassModel {
+    pub fn for_species(species: TreeSpecies) -> Self {
+        match species {
+            TreeSpecies::NorwaySpruce
+            | TreeSpecies::BlackSpruce
+            | TreeSpecies::SerbianSpruce
+            | TreeSpecies::Fir
+            | TreeSpecies::DouglasFir => SPRUCE,
+            species if species.is_coniferous() => PINE,
+            _ => BIRCH,
+        }
+    }
+
+    /// Biomass of a tree of `diameter` cm at breast height and `height` m
+    pub fn tree(&self, diameter: f64, height: f64) -> Biomass {
+        if diameter <= 0.0 || height <= 0.0 {
+            return Biomass::default();
+        }
+        Biomass {
+            above_ground: self.above_ground.biomass(diameter, height),
+            below_ground: self.stump.biomass(diameter, height) + self.roots.biomass(diameter, height),
+        }
+    }
+
+    /// Biomass of `volume` m³ of stems of trees like the mean tree of `diameter` cm and `height` m.
+    /// The volume is expanded with the biomass per stem volume of the mean tree.
+    pub fn from_volume(&self, volume: f64, diameter: f64, height: f64) -> Biomass {
+        let tree_volume = self.stem_volume.volume(diameter, height);
+        if tree_volume <= 0.0 {
+            return Biomass::default();
+        }
+        self.tree(diameter, height) * (volume / tree_volume)
+    }
+}
+
+/// Dry biomass in kg
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Biomass {
+    pub above_ground: f64,
+    pub below_ground: f64,
+}
+
+impl Biomass {
+    pub fn total(&self) -> f64 {
+        self.above_ground + self.below_ground
+    }
+
+    /// kg of carbon
+    pub fn carbon(&self) -> f64 {
+        self.total() * CARBON_FRACTION
+    }
+
+    /// kg of CO₂ equivalent to the carbon
+    pub fn co2(&self) -> f64 {
+        self.carbon() * CO2_PER_CARBON
+    }
+
+    pub fn per_hectare(&self, area: f64) -> Biomass {
+        if area <= 0.0 {
+            return Biomass::default();
+        }
+        Biomass {
+            above_ground: self.above_ground / area,
+            below_ground: self.below_ground / area,
+        }
+    }
+
+    // Properties for GeoJSON features
+    pub fn to_properties(&self, properties: &mut serde_json::Map<String, serde_json::Value>) {
+        properties.insert("above_ground_biomass".to_string(), serde_json::json!(self.above_ground));
+        properties.insert("below_ground_biomass".to_string(), serde_json::json!(self.below_ground));
+        properties.insert("carbon".to_string(), serde_json::json!(self.carbon()));
+        properties.insert("co2".to_string(), serde_json::json!(self.co2()));
+    }
+}
+
+impl Add for Biomass {
+    type Output = Biomass;
+
+    fn add(self, other: Biomass) -> Biomass {
+        Biomass {
+            above_ground: self.above_ground + other.above_ground,
+            below_ground: self.below_ground + other.below_ground,
+        }
+    }
+}
+
+impl Mul<f64> for Biomass {
+    type Output = Biomass;
+
+    fn mul(self, factor: f64) -> Biomass {
+        Biomass {
+            above_ground: self.above_ground * factor,
+            below_ground: self.below_ground * factor,
+        }
+    }
+}
+
+impl AddAssign for Biomass {
+    fn add_assign(&mut self, other: Biomass) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for Biomass {
+    fn sum<I: Iterator<Item = Biomass>>(iter: I) -> Biomass {
+        iter.fold(Biomass::default(), |sum, biomass| sum + biomass)
+    }
+}
+
+/// Biomass of a tree from its diameter and height. Trees without a diameter have no biomass.
+pub fn tree_biomass(tree: &Tree) -> Biomass {
+    BiomassModel::for_species(tree.species()).tree(tree.diameter() as f64, tree.height() as f64)
+}
+
+/// Biomass of a stratum on `area` hectares. The stratum volume (m³/ha) is expanded with the
+/// biomass per stem volume of the stratum's mean tree. Strata without a volume, e.g. seedling
+/// stands, get the biomass of their mean tree times their stem count (per hectare).
+pub fn stratum_biomass(stratum: &TreeStratum, area: f64) -> Biomass {
+    let model = BiomassModel::for_species(stratum.tree_species);
+    let (diameter, height) = (stratum.mean_diameter as f64, stratum.mean_height as f64);
+    if stratum.volume > 0.0 {
+        model.from_volume(stratum.volume as f64 * area, diameter, height)
+    } else {
+        model.tree(diameter, height) * (stratum.stem_count as f64 * area)
+    }
+}
+
+/// Biomass of one stand
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StandBiomass {
+    pub stand_id: String,
+    pub stand_number: String,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+    /// Hectares
+    pub area: f64,
+    pub biomass: Biomass,
+    pub per_hectare: Biomass,
+}
+
+/// Biomass of the stands of a property and their total
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BiomassSummary {
+    pub stands: Vec<StandBiomass>,
+    pub total: Biomass,
+}
+
+impl BiomassSummary {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl Stand {
+    /// Biomass of the whole stand from the strata of the selected snapshot
+    pub fn biomass_for(&self, selector: &SnapshotSelector) -> Biomass {
+        let area = self.stand_basic_data.area as f64;
+        self.get_strata_for(selector)
+            .map(|strata| strata.tree_stratum.iter().map(|stratum| stratum_biomass(stratum, area)).sum())
+            .unwrap_or_default()
+    }
+}
+
+impl ForestPropertyData {
+    pub fn biomass_summary(&self, selector: &SnapshotSelector) -> BiomassSummary {
+        let stands: Vec<StandBiomass> = self.get_stands()
+            .iter()
+            .map(|stand| {
+                let basic_data = &stand.stand_basic_data;
+                let area = basic_data.area as f64;
+                let biomass = stand.biomass_for(selector);
+                StandBiomass {
+                    stand_id: stand.id.to_owned(),
+                    stand_number: format!("{}{}", basic_data.stand_number, basic_data.stand_number_extension),
+                    real_estate_id: stand.real_estate_id,
+                    parcel_number: stand.parcel_number,
+                    area,
+                    biomass,
+                    per_hectare: biomass.per_hectare(area),
+                }
+            })
+            .collect();
+        let total = stands.iter().map(|stand| stand.biomass).sum();
+
+        BiomassSummary { stands, total }
+    }
+}
+
+#[test]
+fn test_biomass() {
+    // A pine of 20 cm and 18 m has a stem of about 0.27 m³ and 140 kg of above ground biomass
+    let pine = BiomassModel::for_species(TreeSpecies::ScotsPine);
+    let volume = pine.stem_volume.volume(20.0, 18.0);
+    let biomass = pine.tree(20.0, 18.0);
+    assert!((0.26..0.29).contains(&volume), "{}", volume);
+    assert!((130.0..155.0).contains(&biomass.above_ground), "{}", biomass.above_ground);
+    assert!((0.15..0.35).contains(&(biomass.below_ground / biomass.above_ground)));
+    assert_eq!(BiomassModel::for_species(TreeSpecies::DouglasFir), BiomassModel::for_species(TreeSpecies::NorwaySpruce));
+    assert_eq!(BiomassModel::for_species(TreeSpecies::Aspen), BiomassModel::for_species(TreeSpecies::SilverBirch));
+
+    // Stratum volume is expanded with the biomass per stem volume of the mean tree
+    let stratum = TreeStratum { tree_species: TreeSpecies::ScotsPine, volume: 100.0, mean_diameter: 20.0, mean_height: 18.0, ..Default::default() };
+    let stratum_total = stratum_biomass(&stratum, 2.0);
+    assert!((stratum_total.above_ground - biomass.above_ground * 200.0 / volume).abs() < 1e-6 * stratum_total.above_ground);
+    assert!((stratum_total.co2() - stratum_total.total() * 0.5 * 44.0 / 12.0).abs() < 1e-6);
+    let seedlings = TreeStratum { stem_count: 2000, mean_diameter: 1.0, mean_height: 2.0, ..stratum };
+    assert!(stratum_biomass(&TreeStratum { volume: 0.0, ..seedlings }, 1.0).total() > 0.0);
+
+    let tree = Tree::new(TreeSpecies::NorwaySpruce, 20.0, (0.0, 0.0, 0.0)).with_dimensions(25.0, 20.0);
+    assert!(tree_biomass(&tree).carbon() > 0.0);
+    assert_eq!(tree_biomass(&Tree::new(TreeSpecies::NorwaySpruce, 20.0, (0.0, 0.0, 0.0))), Biomass::default());
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let summary = property.biomass_summary(&SnapshotSelector::default());
+    let total: f64 = summary.stands.iter().map(|stand| stand.biomass.total()).sum();
+    assert!(summary.total.total() > 0.0 && (summary.total.total() - total).abs() < 1e-6 * total);
+}
@@ -0,0 +1,413 @@
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Languages of the code list names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Finnish,
+    Swedish,
+    English,
+    Latin,
+}
+
+// Generates an enum for a numeric Forest Data code list. Codes that are not in the list
+// are kept in `Unknown`, so that reading and writing a file doesn't lose them.
+// Names are given in Finnish, Swedish, English and Latin, an empty name means there is none.
+macro_rules! code_list {
+    (
+        $(#[$meta:meta])*
+        $name:ident: $repr:ty {
+            $($code:literal => $variant:ident: $fi:literal, $sv:literal, $en:literal, $la:literal;)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown($repr),
+        }
+
+        impl $name {
+            pub fn from_code(code: $repr) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    _ => $name::Unknown(code),
+                }
+            }
+
+            pub fn code(&self) -> $repr {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Unknown(code) => *code,
+                }
+            }
+
+            pub fn is_known(&self) -> bool {
+                !matches!(self, $name::Unknown(_))
+            }
+
+            pub fn name(&self, language: Language) -> Option<&'static str> {
+                let (fi, sv, en, la): (&'static str, &'static str, &'static str, &'static str) = match self {
+                    $($name::$variant => ($fi, $sv, $en, $la),)*
+                    $name::Unknown(_) => ("", "", "", ""),
+                };
+                let name = match language {
+                    Language::Finnish => fi,
+                    Language::Swedish => sv,
+                    Language::English => en,
+                    Language::Latin => la,
+                };
+                if name.is_empty() { None } else { Some(name) }
+            }
+
+            pub fn all() -> &'static [$name] {
+                &[$($name::$variant,)*]
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::Unknown(0)
+            }
+        }
+
+        impl From<$repr> for $name {
+            fn from(code: $repr) -> Self {
+                $name::from_code(code)
+            }
+        }
+
+        // English name, or the code if it is not in the list
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.name(Language::English) {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "{} {}", stringify!($name), self.code()),
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(code: &str) -> Result<Self, Self::Err> {
+                code.trim().parse::<$repr>().map($name::from_code)
+            }
+        }
+
+        // Codes are (de)serialized as the plain code, as in the XML
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.code().serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <$repr>::deserialize(deserializer).map($name::from_code)
+            }
+        }
+    };
+}
+
+// Deserializes an optional code element that may be present but empty, e.g. `<op:TreeSpecies />`
+pub(crate) fn deserialize_optional_code<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(code) if !code.trim().is_empty() => code.parse().map(Some).map_err(de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+code_list! {
+    /// Tree species (puulaji)
+    TreeSpecies: u8 {
+        1 => ScotsPine: "Mänty", "Tall", "Scots pine", "Pinus sylvestris";
+        2 => NorwaySpruce: "Kuusi", "Gran", "Norway spruce", "Picea abies";
+        3 => SilverBirch: "Rauduskoivu", "Vårtbjörk", "Silver birch", "Betula pendula";
+        4 => DownyBirch: "Hieskoivu", "Glasbjörk", "Downy birch", "Betula pubescens";
+        5 => Aspen: "Haapa", "Asp", "Aspen", "Populus tremula";
+        6 => GreyAlder: "Harmaaleppä", "Gråal", "Grey alder", "Alnus incana";
+        7 => BlackAlder: "Tervaleppä", "Klibbal", "Black alder", "Alnus glutinosa";
+        8 => OtherConiferous: "Muu havupuu", "Övrigt barrträd", "Other coniferous", "";
+        9 => OtherDeciduous: "Muu lehtipuu", "Övrigt lövträd", "Other deciduous", "";
+        10 => DouglasFir: "Douglaskuusi", "Douglasgran", "Douglas fir", "Pseudotsuga menziesii";
+        11 => Juniper: "Kataja", "En", "Juniper", "Juniperus communis";
+        12 => LodgepolePine: "Kontortamänty", "Contortatall", "Lodgepole pine", "Pinus contorta";
+        13 => EuropeanWhiteElm: "Kynäjalava", "Vresalm", "European white elm", "Ulmus laevis";
+        14 => Larch: "Lehtikuusi", "Lärk", "Larch", "Larix";
+        15 => SmallLeavedLime: "Metsälehmus", "Skogslind", "Small-leaved lime", "Tilia cordata";
+        16 => BlackSpruce: "Mustakuusi", "Svartgran", "Black spruce", "Picea mariana";
+        17 => Willow: "Paju", "Vide", "Willow", "Salix";
+        18 => Rowan: "Pihlaja", "Rönn", "Rowan", "Sorbus aucuparia";
+        19 => Fir: "Pihta", "Ädelgran", "Fir", "Abies";
+        20 => GoatWillow: "Raita", "Sälg", "Goat willow", "Salix caprea";
+        21 => Ash: "Saarni", "Ask", "Ash", "Fraxinus excelsior";
+        22 => SwissStonePine: "Sembramänty", "Cembratall", "Swiss stone pine", "Pinus cembra";
+        23 => SerbianSpruce: "Serbiankuusi", "Serbisk gran", "Serbian spruce", "Picea omorika";
+        24 => Oak: "Tammi", "Ek", "Oak", "Quercus robur";
+        25 => BirdCherry: "Tuomi", "Hägg", "Bird cherry", "Prunus padus";
+        26 => Maple: "Vaahtera", "Lönn", "Maple", "Acer platanoides";
+        27 => CurlyBirch: "Visakoivu", "Masurbjörk", "Curly birch", "Betula pendula var. carelica";
+        28 => WychElm: "Vuorijalava", "Skogsalm", "Wych elm", "Ulmus glabra";
+        29 => Deciduous: "Lehtipuu", "Lövträd", "Deciduous", "";
+        30 => Coniferous: "Havupuu", "Barrträd", "Coniferous", "";
+    }
+}
+
+impl TreeSpecies {
+    pub fn is_coniferous(&self) -> bool {
+        matches!(
+            self,
+            TreeSpecies::ScotsPine
+                | TreeSpecies::NorwaySpruce
+                | TreeSpecies::OtherConiferous
+                | TreeSpecies::DouglasFir
+                | TreeSpecies::Juniper
+                | TreeSpecies::LodgepolePine
+                | TreeSpecies::Larch
+                | TreeSpecies::BlackSpruce
+                | TreeSpecies::Fir
+                | TreeSpecies::SwissStonePine
+                | TreeSpecies::SerbianSpruce
+                | TreeSpecies::Coniferous
+        )
+    }
+}
+
+code_list! {
+    /// Soil type (maalaji)
+    SoilType: u8 {
+        10 => MediumCoarseMineralSoil: "Keskikarkea tai karkea kangasmaa", "Grov eller medelgrov moränmark", "Medium-coarse or coarse mineral soil", "";
+        11 => CoarseTill: "Karkea moreeni", "Grov morän", "Coarse till", "";
+        12 => CoarseSortedSoil: "Karkea lajittunut maalaji", "Grov sorterad jordart", "Coarse sorted soil", "";
+        20 => FineMineralSoil: "Hienojakoinen kangasmaa", "Finkornig moränmark", "Fine-grained mineral soil", "";
+        21 => FineTill: "Hienojakoinen moreeni", "Finkornig morän", "Fine till", "";
+        22 => FineSortedSoil: "Hienojakoinen lajittunut maalaji", "Finkornig sorterad jordart", "Fine sorted soil", "";
+        23 => SiltySoil: "Silttipitoinen maalaji", "Siltig jordart", "Silty soil", "";
+        24 => Clay: "Savimaa", "Lerjord", "Clay soil", "";
+        30 => StonyCoarseMineralSoil: "Kivinen keskikarkea tai karkea kangasmaa", "Stenig grov eller medelgrov moränmark", "Stony medium-coarse or coarse mineral soil", "";
+        31 => StonyCoarseTill: "Kivinen karkea moreeni", "Stenig grov morän", "Stony coarse till", "";
+        32 => StonyCoarseSortedSoil: "Kivinen karkea lajittunut maalaji", "Stenig grov sorterad jordart", "Stony coarse sorted soil", "";
+        40 => StonyFineMineralSoil: "Kivinen hienojakoinen kangasmaa", "Stenig finkornig moränmark", "Stony fine-grained mineral soil", "";
+        50 => RockOrBoulders: "Kallio tai kivikko", "Berg eller stenbunden mark", "Rock or boulder field", "";
+        60 => Peat: "Turvemaa", "Torvmark", "Peatland", "";
+        61 => SedgePeat: "Saraturve", "Starrtorv", "Sedge peat", "";
+        62 => SphagnumPeat: "Rahkaturve", "Vitmosstorv", "Sphagnum peat", "";
+        63 => WoodyPeat: "Puuvaltainen turve", "Trädtorv", "Woody peat", "";
+        70 => Mould: "Multamaa", "Mylla", "Mould soil", "";
+        80 => Gyttja: "Liejumaa", "Gyttja", "Gyttja soil", "";
+    }
+}
+
+code_list! {
+    /// Fertility class (kasvupaikka)
+    FertilityClass: u8 {
+        1 => HerbRich: "Lehto", "Lund", "Herb-rich forest", "";
+        2 => HerbRichHeath: "Lehtomainen kangas", "Lundartad mo", "Herb-rich heath forest", "";
+        3 => Mesic: "Tuore kangas", "Frisk mo", "Mesic heath forest", "";
+        4 => SubXeric: "Kuivahko kangas", "Torrare mo", "Sub-xeric heath forest", "";
+        5 => Xeric: "Kuiva kangas", "Torr mo", "Xeric heath forest", "";
+        6 => Barren: "Karukkokangas", "Karg mo", "Barren heath forest", "";
+        7 => RockyOrSandy: "Kalliomaa ja hietikko", "Berg- och sandmark", "Rocky or sandy soil", "";
+        8 => SummitOrFell: "Lakimetsä ja tunturi", "Topp- och fjällskog", "Summit forest or fell", "";
+    }
+}
+
+code_list! {
+    /// Drainage state (ojitustilanne)
+    DrainageState: u8 {
+        1 => UndrainedMineralSoil: "Ojittamaton kangas", "Odikad mo", "Undrained mineral soil", "";
+        2 => DrainedMineralSoil: "Ojitettu kangas", "Dikad mo", "Drained mineral soil", "";
+        3 => PaludifiedMineralSoil: "Soistunut kangas", "Försumpad mo", "Paludified mineral soil", "";
+        6 => UndrainedPeatland: "Ojittamaton suo", "Odikad myr", "Undrained peatland", "";
+        7 => RecentlyDrainedPeatland: "Ojikko", "Nydikad myr", "Recently drained peatland", "";
+        8 => TransformingPeatland: "Muuttuma", "Omvandlingsmyr", "Transforming drained peatland", "";
+        9 => PeatlandForest: "Turvekangas", "Torvmo", "Drained peatland forest", "";
+    }
+}
+
+code_list! {
+    /// Main land use group (pääryhmä)
+    MainGroup: u8 {
+        1 => ForestLand: "Metsämaa", "Skogsmark", "Forest land", "";
+        2 => PoorlyProductiveForestLand: "Kitumaa", "Tvinmark", "Poorly productive forest land", "";
+        3 => UnproductiveLand: "Joutomaa", "Impediment", "Unproductive land", "";
+        4 => OtherForestryLand: "Muu metsätalousmaa", "Annan skogsbruksmark", "Other forestry land", "";
+        5 => BuildingSite: "Tontti", "Tomt", "Building site", "";
+        6 => AgriculturalLand: "Maatalousmaa", "Jordbruksmark", "Agricultural land", "";
+        7 => OtherLand: "Muu maa", "Annan mark", "Other land", "";
+        8 => Water: "Vesistö", "Vattendrag", "Water", "";
+    }
+}
+
+code_list! {
+    /// Storey of a tree stratum (jakso)
+    Storey: u8 {
+        1 => Dominant: "Valtajakso", "Huvudskikt", "Dominant storey", "";
+        2 => Undergrowth: "Alikasvos", "Underväxt", "Undergrowth", "";
+        3 => Overstorey: "Ylispuusto", "Överståndare", "Overstorey", "";
+    }
+}
+
+code_list! {
+    /// Type of dead trees (kuolleen puun tyyppi)
+    DeadTreeType: u8 {
+        1 => Standing: "Pystypuu", "Stående träd", "Standing dead tree", "";
+        2 => Fallen: "Maapuu", "Liggande träd", "Fallen dead tree", "";
+        3 => Kelo: "Keloutunut pystypuu", "Torraka", "Standing kelo tree", "";
+    }
+}
+
+code_list! {
+    /// Operation type (toimenpidelaji). Codes below 100 are cuttings, others silvicultural work.
+    OperationType: u16 {
+        1 => OverstoreyRemoval: "Ylispuiden poisto", "Avverkning av överståndare", "Removal of overstorey", "";
+        2 => FirstThinning: "Ensiharvennus", "Första gallring", "First thinning", "";
+        3 => Thinning: "Harvennushakkuu", "Gallring", "Thinning", "";
+        5 => ClearCutting: "Avohakkuu", "Kalavverkning", "Clear cutting", "";
+        6 => ShelterwoodCutting: "Suojuspuuhakkuu", "Skärmträdsavverkning", "Shelterwood cutting", "";
+        7 => SeedTreeCutting: "Siemenpuuhakkuu", "Fröträdsavverkning", "Seed tree cutting", "";
+    }
+}
+
+impl OperationType {
+    pub fn is_cutting(&self) -> bool {
+        self.code() < 100
+    }
+
+    /// Cuttings that remove the whole tree stand, apart from retained seed or shelter trees
+    pub fn is_regeneration_cutting(&self) -> bool {
+        matches!(
+            self,
+            OperationType::ClearCutting | OperationType::ShelterwoodCutting | OperationType::SeedTreeCutting
+        )
+    }
+}
+
+code_list! {
+    /// Change state of an element since the data was delivered (muutostila)
+    ChangeState: u8 {
+        0 => Unchanged: "Ei muutosta", "Ingen ändring", "Unchanged", "";
+        1 => Added: "Lisätty", "Tillagd", "Added", "";
+        2 => Modified: "Muutettu", "Ändrad", "Modified", "";
+        3 => Removed: "Poistettu", "Borttagen", "Removed", "";
+    }
+}
+
+code_list! {
+    /// Type of a dated tree stand data snapshot (puustotiedon tyyppi)
+    TreeStandDataType: u8 {
+        1 => Inventory: "Inventointi", "Inventering", "Inventory", "";
+        2 => Updated: "Ajantasaistus", "Uppdatering", "Updated", "";
+        3 => Forecast: "Ennuste", "Prognos", "Forecast", "";
+    }
+}
+
+/// Development class (kehitysluokka). Unlike the other code lists the codes are strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DevelopmentClass {
+    OpenArea,
+    SeedTreeStand,
+    YoungSeedlingStand,
+    AdvancedSeedlingStand,
+    YoungThinningStand,
+    AdvancedThinningStand,
+    MatureStand,
+    ShelterwoodStand,
+    SeedlingStandWithOverstorey,
+    UnevenAged,
+    Unknown(String),
+}
+
+const DEVELOPMENT_CLASSES: [(&str, DevelopmentClass, [&str; 3]); 10] = [
+    ("A0", DevelopmentClass::OpenArea, ["Aukea", "Kalmark", "Open area"]),
+    ("S0", DevelopmentClass::SeedTreeStand, ["Siemenpuumetsikkö", "Fröträdsställning", "Seed tree stand"]),
+    ("T1", DevelopmentClass::YoungSeedlingStand, ["Taimikko alle 1,3 m", "Plantbestånd under 1,3 m", "Young seedling stand"]),
+    ("T2", DevelopmentClass::AdvancedSeedlingStand, ["Taimikko yli 1,3 m", "Plantbestånd över 1,3 m", "Advanced seedling stand"]),
+    ("02", DevelopmentClass::YoungThinningStand, ["Nuori kasvatusmetsikkö", "Ungt gallringsbestånd", "Young thinning stand"]),
+    ("03", DevelopmentClass::AdvancedThinningStand, ["Varttunut kasvatusmetsikkö", "Äldre gallringsbestånd", "Advanced thinning stand"]),
+    ("04", DevelopmentClass::MatureStand, ["Uudistuskypsä metsikkö", "Förnyelsemoget bestånd", "Mature stand"]),
+    ("05", DevelopmentClass::ShelterwoodStand, ["Suojuspuumetsikkö", "Skärmträdsställning", "Shelterwood stand"]),
+    ("Y1", DevelopmentClass::SeedlingStandWithOverstorey, ["Ylispuustoinen taimikko", "Plantbestånd med överståndare", "Seedling stand with overstorey"]),
+    ("ER", DevelopmentClass::UnevenAged, ["Eri-ikäisrakenteinen metsä", "Olikåldrigt bestånd", "Uneven-aged stand"]),
+];
+
+impl DevelopmentClass {
+    pub fn from_code(code: &str) -> Self {
+        DEVELOPMENT_CLASSES.iter()
+            .find(|(known, _, _)| *known == code)
+            .map(|(_, class, _)| class.to_owned())
+            .unwrap_or_else(|| DevelopmentClass::Unknown(code.to_string()))
+    }
+
+    pub fn code(&self) -> &str {
+        match self {
+            DevelopmentClass::Unknown(code) => code,
+            class => DEVELOPMENT_CLASSES.iter()
+                .find(|(_, known, _)| known == class)
+                .map(|(code, _, _)| *code)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, DevelopmentClass::Unknown(_))
+    }
+
+    pub fn name(&self, language: Language) -> Option<&'static str> {
+        let (_, _, names) = DEVELOPMENT_CLASSES.iter().find(|(_, known, _)| known == self)?;
+        match language {
+            Language::Finnish => Some(names[0]),
+            Language::Swedish => Some(names[1]),
+            Language::English => Some(names[2]),
+            Language::Latin => None,
+        }
+    }
+}
+
+impl fmt::Display for DevelopmentClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name(Language::English) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "DevelopmentClass {}", self.code()),
+        }
+    }
+}
+
+impl Serialize for DevelopmentClass {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for DevelopmentClass {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|code| DevelopmentClass::from_code(&code))
+    }
+}
+
+#[test]
+fn test_code_lists() {
+    assert_eq!(TreeSpecies::from_code(2), TreeSpecies::NorwaySpruce);
+    assert_eq!(TreeSpecies::NorwaySpruce.name(Language::Finnish), Some("Kuusi"));
+    assert_eq!(TreeSpecies::NorwaySpruce.name(Language::Latin), Some("Picea abies"));
+    assert_eq!(TreeSpecies::from_code(103), TreeSpecies::Unknown(103));
+    assert_eq!(TreeSpecies::from_code(103).code(), 103);
+    assert_eq!(TreeSpecies::from_code(103).to_string(), "TreeSpecies 103");
+
+    assert_eq!(DevelopmentClass::from_code("02"), DevelopmentClass::YoungThinningStand);
+    assert_eq!(DevelopmentClass::from_code("X9").code(), "X9");
+    assert_eq!(OperationType::from_code(940).code(), 940);
+    assert!(OperationType::ClearCutting.is_cutting());
+
+    // Codes survive a serde round trip
+    let species: Vec<TreeSpecies> = serde_json::from_str("[1, 103]").unwrap();
+    assert_eq!(serde_json::to_string(&species).unwrap(), "[1,103]");
+}
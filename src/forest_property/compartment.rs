@@ -1,17 +1,35 @@
+use crate::error::ForestDataError;
 use crate::forest_property::tree::Tree;
-use crate::geometry_utils::generate_random_trees;
+use crate::geometry_utils::{clip_polygons_to_bounding_box, clip_trees, generate_stratum_trees_with_report, mix_seed, stand_seed, DEFAULT_SEED};
+use crate::sampling::{FillReport, SamplingOptions};
+use super::biomass::{stratum_biomass, Biomass};
+use super::dead_wood::{clip_dead_trees, generate_dead_wood, DeadTree, DeadWoodReport};
+use super::forest_property_data::SnapshotSelector;
+use super::special_features::FeatureRules;
 use super::stand::Stand;
+use super::tree_stand_data::TreeStrata;
 
 use geo::{Polygon, Area, BooleanOps};
 use geo::Intersects;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 // Struct that represents a stand of trees
 #[derive(Debug, Clone)]
 pub struct Compartment {
     pub stand_number: String,
     pub trees: Vec<Tree>,
+    pub dead_trees: Vec<DeadTree>,
+    // Biomass of the strata on the area of the compartment
+    pub biomass: Biomass,
+    // Feature codes of the special features of the stand
+    pub special_feature_codes: Vec<String>,
     pub polygon: Polygon,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
 }
 
 impl Compartment {
@@ -19,7 +37,12 @@ impl Compartment {
         Compartment {
             stand_number,
             trees,
+            dead_trees: Vec::new(),
+            biomass: Biomass::default(),
+            special_feature_codes: Vec::new(),
             polygon,
+            real_estate_id: None,
+            parcel_number: None,
         }
     }
 
@@ -27,10 +50,22 @@ impl Compartment {
         &self.stand_number
     }
 
+    pub fn real_estate_id(&self) -> Option<u32> {
+        self.real_estate_id
+    }
+
+    pub fn parcel_number(&self) -> Option<i64> {
+        self.parcel_number
+    }
+
     pub fn trees(&self) -> &Vec<Tree> {
         &self.trees
     }
 
+    pub fn dead_trees(&self) -> &Vec<DeadTree> {
+        &self.dead_trees
+    }
+
     pub fn polygon(&self) -> &Polygon {
         &self.polygon
     }
@@ -57,13 +92,23 @@ impl Compartment {
     }
 }
 
-pub fn find_stands_in_bounding_box<'a>(stands: &'a Vec<Stand>, bbox: &'a Polygon) -> Option<Vec<&'a Stand>> {
+// Check if the exterior of any part of a stand intersects with the bounding box
+pub fn stand_intersects_bounding_box(stand: &Stand, bbox: &Polygon) -> bool {
+    match &stand.computed_multi_polygon {
+        Some(multi_polygon) => multi_polygon.iter().any(|polygon| bbox.intersects(polygon.exterior())),
+        None => {
+            let (exterior, _) = stand.get_geometries();
+            bbox.intersects(&exterior)
+        }
+    }
+}
+
+pub fn find_stands_in_bounding_box<'a>(stands: &'a [Stand], bbox: &'a Polygon) -> Option<Vec<&'a Stand>> {
 
     // Collect the stands that intersect with the bounding box
-    let intersecting_stands: Vec<&Stand> = stands.iter().filter(|stand| {
-        let (exterior, _) = stand.get_geometries();
-        bbox.intersects(&exterior)
-    }).collect();  // Collect the stands that intersect with the bounding box
+    let intersecting_stands: Vec<&Stand> = stands.iter()
+        .filter(|stand| stand_intersects_bounding_box(stand, bbox))
+        .collect();
 
     if intersecting_stands.is_empty() {
         println!("No stands found in the bounding box");
@@ -73,57 +118,420 @@ pub fn find_stands_in_bounding_box<'a>(stands: &'a Vec<Stand>, bbox: &'a Polygon
     }
 }
 
+// Options for generating the trees of compartments
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GenerationOptions {
+    // Tree stand data snapshot the trees are generated from
+    pub snapshot: SnapshotSelector,
+    // Rules for stands with special features, e.g. no living trees in some zones
+    pub feature_rules: FeatureRules,
+    // Base seed of the random generators. Stands get their own seeds derived from it and their ids,
+    // so the same seed gives the same trees. None uses `DEFAULT_SEED`.
+    pub seed: Option<u64>,
+    // Point processes of the trees by stand and species
+    pub sampling: SamplingOptions,
+}
+
+// Key of the dead wood generator, mixed into the stand's seed
+const DEAD_WOOD_SEED_KEY: u64 = u64::MAX;
+
+// Parts of the stand with their shares of its area. The stem counts and volumes of the stand
+// are split between the parts by area.
+fn stand_parts(stand: &Stand) -> Vec<(&Polygon, f64)> {
+    let parts = stand.polygons();
+    let total_area: f64 = parts.iter().map(|part| part.unsigned_area()).sum();
+    parts
+        .iter()
+        .map(|part| {
+            let share = if total_area > 0.0 { part.unsigned_area() / total_area } else { 1.0 / parts.len() as f64 };
+            (part, share)
+        })
+        .collect()
+}
+
+// Seed of a part of a stand. The first part keeps the stand's seed, so a stand of one polygon
+// gets the same trees whether it is generated as a whole or by parts.
+fn part_seed(seed: u64, part: usize) -> u64 {
+    if part == 0 { seed } else { mix_seed(seed, part as u64) }
+}
+
+// Trees of every stratum over all parts of the stand, in the order of the strata
+pub fn stand_stratum_trees(stand: &Stand, strata: &TreeStrata, options: &GenerationOptions) -> (Vec<Vec<Tree>>, FillReport) {
+    let seed = options.stand_seed(stand);
+    let mut trees = vec![Vec::new(); strata.tree_stratum.len()];
+    let mut report = FillReport::default();
+
+    for (index, (part, share)) in stand_parts(stand).into_iter().enumerate() {
+        let (part_trees, part_report) =
+            generate_stratum_trees_with_report(part, strata, share, part_seed(seed, index), &options.sampling, &stand.id);
+        for (stratum_trees, part_trees) in trees.iter_mut().zip(part_trees) {
+            stratum_trees.extend(part_trees);
+        }
+        report.merge(part_report);
+    }
+
+    (trees, report)
+}
+
+impl GenerationOptions {
+    pub fn with_seed(seed: u64) -> Self {
+        GenerationOptions { seed: Some(seed), ..Default::default() }
+    }
+
+    // Seed of a stand's generators
+    pub fn stand_seed(&self, stand: &Stand) -> u64 {
+        stand_seed(self.seed.unwrap_or(DEFAULT_SEED), &stand.id)
+    }
+}
+
+// Trees and dead trees of a whole stand. They depend only on the stand and the options,
+// so every bounding box query clips the same trees.
+#[derive(Debug, Clone, Default)]
+pub struct StandTrees {
+    pub trees: Vec<Tree>,
+    pub dead_trees: Vec<DeadTree>,
+    // Target and achieved tree counts of the strata of the whole stand
+    pub fill_report: FillReport,
+    // Target and achieved dead trees of the dead tree strata of the whole stand
+    pub dead_wood_report: DeadWoodReport,
+}
+
+impl StandTrees {
+    // Generates the trees over every part of the stand's polygon. Empty if the stand has no polygon.
+    pub fn generate(stand: &Stand, options: &GenerationOptions) -> Self {
+        if stand.polygons().is_empty() {
+            return StandTrees::default();
+        }
+
+        // Generate trees if strata exist and the stand's special features allow it
+        let (trees, fill_report) = match stand.get_strata_for(&options.snapshot) {
+            Some(strata) if !options.feature_rules.zone(stand).skip_tree_generation => {
+                let (trees, fill_report) = stand_stratum_trees(stand, &strata, options);
+                (trees.into_iter().flatten().collect(), fill_report)
+            }
+            _ => (vec![], FillReport::default()),
+        };
+
+        let (dead_trees, dead_wood_report) = StandTrees::generate_dead_trees(stand, options);
+
+        StandTrees { trees, dead_trees, fill_report, dead_wood_report }
+    }
+
+    // Dead trees of the stand's full polygon alone, the same ones `generate` gives
+    pub fn generate_dead_trees(stand: &Stand, options: &GenerationOptions) -> (Vec<DeadTree>, DeadWoodReport) {
+        let dead_tree_strata = stand.dead_tree_strata_for(&options.snapshot);
+        let area = stand.stand_basic_data.area as f64;
+        let mut rng = StdRng::seed_from_u64(mix_seed(options.stand_seed(stand), DEAD_WOOD_SEED_KEY));
+        let mut dead_trees = Vec::new();
+        let mut report = DeadWoodReport::default();
+
+        for (part, share) in stand_parts(stand) {
+            let (part_dead_trees, part_report) = generate_dead_wood(part, dead_tree_strata, area * share, &mut rng);
+            dead_trees.extend(part_dead_trees);
+            report.merge(part_report);
+        }
+
+        (dead_trees, report)
+    }
+
+    // Trees standing inside the polygon. Fallen logs are kept if their root end is inside.
+    pub fn clip(&self, polygon: &Polygon) -> StandTrees {
+        StandTrees {
+            trees: clip_trees(&self.trees, polygon),
+            dead_trees: clip_dead_trees(&self.dead_trees, polygon),
+            fill_report: self.fill_report.clone(),
+            dead_wood_report: self.dead_wood_report.clone(),
+        }
+    }
+}
+
+// Stand trees generated once per stand id and shared between queries with the same options
+#[derive(Debug, Default)]
+pub struct TreeCache {
+    options: GenerationOptions,
+    stands: Mutex<HashMap<String, Arc<StandTrees>>>,
+}
+
+impl TreeCache {
+    pub fn new(options: GenerationOptions) -> Self {
+        TreeCache { options, stands: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn options(&self) -> &GenerationOptions {
+        &self.options
+    }
+
+    pub fn stand_trees(&self, stand: &Stand) -> Arc<StandTrees> {
+        if let Some(trees) = self.stands.lock().unwrap().get(&stand.id) {
+            return Arc::clone(trees);
+        }
+
+        // Generated without holding the lock so that stands are generated in parallel
+        let trees = Arc::new(StandTrees::generate(stand, &self.options));
+        Arc::clone(self.stands.lock().unwrap().entry(stand.id.to_owned()).or_insert(trees))
+    }
+
+    pub fn len(&self) -> usize {
+        self.stands.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.stands.lock().unwrap().clear();
+    }
+}
+
+// Clips the stand to the bounding box and generates its trees. Every part of the clipped polygon is a
+// compartment of its own. Empty if the stand has no polygon or the clipped polygon is empty, e.g. for invalid geometry.
+pub fn create_compartments_in_bounding_box(stand: &Stand, bbox: &Polygon) -> Vec<Compartment> {
+    create_compartments_in_bounding_box_with_options(stand, bbox, &GenerationOptions::default())
+}
+
+pub fn create_compartments_in_bounding_box_with_options(
+    stand: &Stand,
+    bbox: &Polygon,
+    options: &GenerationOptions
+) -> Vec<Compartment> {
+    // Check the clipped polygon before generating the trees of the whole stand
+    if clip_polygons_to_bounding_box(stand.polygons(), bbox).is_empty() {
+        return vec![];
+    }
+    compartments_from_stand_trees(stand, bbox, options, &StandTrees::generate(stand, options))
+}
+
+// Like `create_compartments_in_bounding_box_with_options`, with the stand's trees from the cache
+pub fn create_compartments_in_bounding_box_cached(stand: &Stand, bbox: &Polygon, cache: &TreeCache) -> Vec<Compartment> {
+    if clip_polygons_to_bounding_box(stand.polygons(), bbox).is_empty() {
+        return vec![];
+    }
+    compartments_from_stand_trees(stand, bbox, cache.options(), &cache.stand_trees(stand))
+}
+
+fn compartments_from_stand_trees(
+    stand: &Stand,
+    bbox: &Polygon,
+    options: &GenerationOptions,
+    stand_trees: &StandTrees
+) -> Vec<Compartment> {
+    let stand_area: f64 = stand.polygons().iter().map(|polygon| polygon.unsigned_area()).sum();
+    let strata = match stand.get_strata_for(&options.snapshot) {
+        Some(strata) if !options.feature_rules.zone(stand).skip_tree_generation => Some(strata),
+        _ => None,
+    };
+
+    // Clip the stand's polygons to the bounding box
+    clip_polygons_to_bounding_box(stand.polygons(), bbox)
+        .into_iter()
+        .map(|clipped_polygon| {
+            // Strata values are per hectare, the clipped part gets its share of the stand's area
+            let area = stand.stand_basic_data.area as f64 * clipped_polygon.unsigned_area() / stand_area;
+            let biomass = match &strata {
+                Some(strata) => strata.tree_stratum.iter().map(|stratum| stratum_biomass(stratum, area)).sum(),
+                None => Biomass::default(),
+            };
+            let StandTrees { trees, dead_trees, .. } = stand_trees.clip(&clipped_polygon);
+
+            Compartment {
+                stand_number: stand.stand_basic_data.stand_number.to_string(),
+                trees,
+                dead_trees,
+                biomass,
+                special_feature_codes: stand.special_features().iter().map(|feature| feature.feature_code.trim().to_string()).collect(),
+                polygon: clipped_polygon,
+                real_estate_id: stand.real_estate_id,
+                parcel_number: stand.parcel_number,
+            }
+        })
+        .collect()
+}
+
 // Get compartments in a bounding box.
 pub fn get_compartments_in_bounding_box(
     all_stands: Vec<Stand>,
     bbox: &Polygon
+) -> Vec<Compartment> {
+    get_compartments_in_bounding_box_with_options(all_stands, bbox, &GenerationOptions::default())
+}
+
+pub fn get_compartments_in_bounding_box_with_options(
+    all_stands: Vec<Stand>,
+    bbox: &Polygon,
+    options: &GenerationOptions
 ) -> Vec<Compartment> {
     // Find stands in the bounding box
     let stands = find_stands_in_bounding_box(&all_stands, bbox);
 
     // If there are stands in the bounding box, generate random trees for each stand
-    if !&stands.is_none() {
-        let compartments: Vec<Compartment> = stands.unwrap()
+    match stands {
+        Some(stands) => stands
             .into_par_iter()
-            .map(|stand| {
-                let polygon = stand.computed_polygon.to_owned().unwrap();
-                let strata = stand.get_strata();
-
-                // Clip the stand's polygon to the bounding box
-                let intersected_polygons = polygon.intersection(bbox).0;
-                let clipped_polygon = intersected_polygons.first()
-                    .expect("Intersection result should contain at least one polygon")
-                    .to_owned();
-                
-                // Calculate the area ratio of the clipped polygon to the original polygon
-                let original_area = polygon.unsigned_area();
-                let clipped_area = clipped_polygon.unsigned_area();
-                let area_ratio = clipped_area / original_area;
-
-                // Generate trees if strata exist
-                let trees = if let Some(strata) = strata {
-                    generate_random_trees(&clipped_polygon, &strata, area_ratio)
-                } else {
-                    vec![]
-                };
-
-                // Create and return the compartment
-                Compartment {
-                    stand_number: stand.stand_basic_data.stand_number.to_string(),
-                    trees,
-                    polygon: clipped_polygon,
-                }
-            })
-            .collect();
-
-        compartments
-    } else {
-        vec![]
+            .flat_map(|stand| create_compartments_in_bounding_box_with_options(stand, bbox, options))
+            .collect(),
+        None => vec![],
     }
 }
 
+// Get compartments in a bounding box, generating the trees of each stand only once per cache
+pub fn get_compartments_in_bounding_box_cached(
+    all_stands: &[Stand],
+    bbox: &Polygon,
+    cache: &TreeCache
+) -> Vec<Compartment> {
+    match find_stands_in_bounding_box(all_stands, bbox) {
+        Some(stands) => stands
+            .into_par_iter()
+            .flat_map(|stand| create_compartments_in_bounding_box_cached(stand, bbox, cache))
+            .collect(),
+        None => vec![],
+    }
+}
+
+// Get compartments in a bounding box from a stream of stands, e.g. a `StandReader`.
+// Only one stand at a time is kept in memory.
+pub fn compartments_in_bounding_box_from_stream<'a, I>(
+    stands: I,
+    bbox: &'a Polygon
+) -> impl Iterator<Item = Result<Compartment, ForestDataError>> + 'a
+where
+    I: Iterator<Item = Result<Stand, ForestDataError>> + 'a,
+{
+    stands.flat_map(move |stand| match stand {
+        Ok(stand) if stand_intersects_bounding_box(&stand, bbox) => {
+            create_compartments_in_bounding_box(&stand, bbox).into_iter().map(Ok).collect()
+        }
+        Ok(_) => vec![],
+        Err(e) => vec![Err(e)],
+    })
+}
+
 pub struct CompartmentArea {
     pub stand_number: String,
     pub polygon: Polygon,
+    pub dead_trees: Vec<DeadTree>,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
 }
 
+
+#[test]
+fn test_compartments_from_stream() {
+    use crate::forest_property::stand_reader::StandReader;
+    use geo::{Coord, LineString};
+
+    let (min_x, min_y) = (25.38, 66.43);
+    let (max_x, max_y) = (min_x + 0.01, min_y + 0.01);
+    let bbox = Polygon::new(
+        LineString(vec![
+            Coord { x: min_x, y: min_y },
+            Coord { x: max_x, y: min_y },
+            Coord { x: max_x, y: max_y },
+            Coord { x: min_x, y: max_y },
+            Coord { x: min_x, y: min_y },
+        ]),
+        vec![],
+    );
+
+    let all_stands: Vec<Stand> = StandReader::from_file("forestpropertydata.xml").unwrap()
+        .stands()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    // One compartment per part of a stand in the bounding box
+    let expected: Vec<String> = find_stands_in_bounding_box(&all_stands, &bbox).unwrap_or_default()
+        .iter()
+        .flat_map(|stand| {
+            let parts = clip_polygons_to_bounding_box(stand.polygons(), &bbox).len();
+            std::iter::repeat(stand.stand_basic_data.stand_number.to_string()).take(parts)
+        })
+        .collect();
+
+    let stands = StandReader::from_file("forestpropertydata.xml").unwrap().stands();
+    let compartments: Vec<Compartment> = compartments_in_bounding_box_from_stream(stands, &bbox)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert!(!expected.is_empty());
+    assert_eq!(compartments.iter().map(|c| c.stand_number.to_owned()).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn test_stable_trees_across_bounding_boxes() {
+    use crate::forest_property::forest_property_data::ForestPropertyData;
+    use geo::{BoundingRect, Coord, Rect};
+
+    let stands = ForestPropertyData::from_xml_file("forestpropertydata.xml").get_stands();
+    let stand = stands.iter()
+        .filter(|stand| stand.computed_polygon.is_some() && stand.summary_stem_count().unwrap_or(0) > 0)
+        .min_by(|a, b| a.stand_basic_data.area.total_cmp(&b.stand_basic_data.area))
+        .unwrap();
+
+    // Two bounding boxes covering the left and right two thirds of the stand
+    let rect = stand.computed_polygon.as_ref().unwrap().bounding_rect().unwrap();
+    let (width, height) = (rect.width(), rect.height());
+    let bbox = |min_x: f64, max_x: f64| {
+        Rect::new(Coord { x: min_x, y: rect.min().y - height }, Coord { x: max_x, y: rect.max().y + height }).to_polygon()
+    };
+    let first = bbox(rect.min().x - width, rect.min().x + width * 2.0 / 3.0);
+    let second = bbox(rect.min().x + width / 3.0, rect.max().x + width);
+    let overlap = bbox(rect.min().x + width / 3.0, rect.min().x + width * 2.0 / 3.0);
+
+    let overlapping_trees = |compartments: Vec<Compartment>| -> Vec<(f64, f64, f64)> {
+        assert!(!compartments.is_empty());
+        let trees: Vec<Tree> = compartments.into_iter().flat_map(|compartment| compartment.trees).collect();
+        clip_trees(&trees, &overlap).iter().map(|tree| tree.position()).collect()
+    };
+    let options = GenerationOptions::default();
+    let from_first = overlapping_trees(create_compartments_in_bounding_box_with_options(stand, &first, &options));
+    let from_second = overlapping_trees(create_compartments_in_bounding_box_with_options(stand, &second, &options));
+    assert!(!from_first.is_empty());
+    assert_eq!(from_first, from_second);
+
+    let cache = TreeCache::new(options);
+    assert_eq!(overlapping_trees(create_compartments_in_bounding_box_cached(stand, &second, &cache)), from_first);
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn test_multi_part_stand() {
+    use crate::forest_property::forest_property_data::ForestPropertyData;
+    use geo::{BoundingRect, Contains, MultiPolygon, Translate};
+
+    let stands = ForestPropertyData::from_xml_file("forestpropertydata.xml").get_stands();
+    let mut stand = stands.iter()
+        .filter(|stand| stand.computed_polygon.is_some() && stand.summary_stem_count().unwrap_or(0) > 0)
+        .min_by(|a, b| a.stand_basic_data.area.total_cmp(&b.stand_basic_data.area))
+        .unwrap()
+        .clone();
+
+    // A second part of the same shape next to the first one
+    let first = stand.computed_polygon.clone().unwrap();
+    let rect = first.bounding_rect().unwrap();
+    let second = first.translate(rect.width() * 2.0, 0.0);
+    stand.computed_multi_polygon = Some(MultiPolygon::new(vec![first.clone(), second.clone()]));
+
+    let options = GenerationOptions::default();
+    let stand_trees = StandTrees::generate(&stand, &options);
+    let in_part = |polygon: &Polygon| stand_trees.trees.iter().filter(|tree| polygon.contains(&geo::Point::new(tree.position().0, tree.position().1))).count();
+    assert!(in_part(&first) > 0 && in_part(&second) > 0);
+    assert_eq!(in_part(&first) + in_part(&second), stand_trees.trees.len());
+    assert_eq!(stand_trees.fill_report.strata.len(), stand.get_strata().unwrap().tree_stratum.len());
+
+    // Every part in the bounding box is a compartment with the trees of its part
+    let bbox = |min_x: f64, max_x: f64| {
+        geo::Rect::new(geo::Coord { x: min_x, y: rect.min().y - 1.0 }, geo::Coord { x: max_x, y: rect.max().y + 1.0 }).to_polygon()
+    };
+    let both = bbox(rect.min().x - 1.0, rect.max().x + 3.0 * rect.width());
+    let compartments = create_compartments_in_bounding_box_with_options(&stand, &both, &options);
+    assert_eq!(compartments.len(), 2);
+    assert_eq!(compartments.iter().map(|compartment| compartment.trees.len()).sum::<usize>(), stand_trees.trees.len());
+    assert!((compartments[0].biomass.above_ground - compartments[1].biomass.above_ground).abs() < 1e-6 * compartments[0].biomass.above_ground.max(1.0));
+
+    // Only the second part is in the bounding box
+    let second_only = bbox(rect.max().x + 0.5 * rect.width(), rect.max().x + 3.0 * rect.width());
+    assert!(stand_intersects_bounding_box(&stand, &second_only));
+    let compartments = create_compartments_in_bounding_box_with_options(&stand, &second_only, &options);
+    assert_eq!(compartments.len(), 1);
+    assert_eq!(compartments[0].trees.len(), in_part(&second));
+}
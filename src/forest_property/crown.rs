@@ -0,0 +1,109 @@
+use serde::Serialize;
+use super::codes::TreeSpecies;
+
+/// Crown shape classes used for drawing and canopy analysis
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CrownShape {
+    /// Narrow crown widest at the base, spruces and firs
+    Conical,
+    /// Crown widest above its middle, pines and larches
+    Ovoid,
+    /// Broad rounded crown of deciduous trees
+    Spherical,
+}
+
+impl CrownShape {
+    pub fn code(&self) -> u8 {
+        match self {
+            CrownShape::Conical => 1,
+            CrownShape::Ovoid => 2,
+            CrownShape::Spherical => 3,
+        }
+    }
+}
+
+// Coefficients of the crown models of one species group. The values in `coefficients` and
+// `CROWN_RATIO_SLOPE` are placeholders that give plausible crown sizes for drawing. They are
+// not taken from or fitted to a published crown model, so crowns are not fit for canopy
+// cover or light calculations until the values are replaced by a published model.
+struct CrownCoefficients {
+    shape: CrownShape,
+    // Crown width (m) = width_intercept + width_slope * dbh (cm)
+    width_intercept: f32,
+    width_slope: f32,
+    // Crown ratio of a tree with a slenderness of 80
+    crown_ratio: f32,
+}
+
+// Slenderness (height in cm / dbh in cm) that gets the group's base crown ratio
+const REFERENCE_SLENDERNESS: f32 = 80.0;
+// Change of the crown ratio per unit of slenderness, slender trees have shorter crowns
+const CROWN_RATIO_SLOPE: f32 = 0.004;
+const MIN_CROWN_RATIO: f32 = 0.15;
+const MAX_CROWN_RATIO: f32 = 0.95;
+
+fn coefficients(species: TreeSpecies) -> CrownCoefficients {
+    match species {
+        TreeSpecies::ScotsPine | TreeSpecies::LodgepolePine | TreeSpecies::SwissStonePine | TreeSpecies::Larch => {
+            CrownCoefficients { shape: CrownShape::Ovoid, width_intercept: 0.85, width_slope: 0.11, crown_ratio: 0.45 }
+        }
+        species if species.is_coniferous() => {
+            CrownCoefficients { shape: CrownShape::Conical, width_intercept: 0.9, width_slope: 0.10, crown_ratio: 0.70 }
+        }
+        TreeSpecies::SilverBirch | TreeSpecies::DownyBirch | TreeSpecies::CurlyBirch => {
+            CrownCoefficients { shape: CrownShape::Spherical, width_intercept: 1.1, width_slope: 0.13, crown_ratio: 0.50 }
+        }
+        _ => CrownCoefficients { shape: CrownShape::Spherical, width_intercept: 1.2, width_slope: 0.14, crown_ratio: 0.55 },
+    }
+}
+
+/// Crown of a single tree
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Crown {
+    /// Metres
+    pub radius: f32,
+    /// Height of the lowest living branches in metres
+    pub base_height: f32,
+    pub shape: CrownShape,
+}
+
+impl Crown {
+    /// Crown of a tree from its species, breast height diameter (cm) and height (m).
+    ///
+    /// Crown width grows linearly with the diameter, cw = a + b·dbh, with coefficients for
+    /// pines, other conifers, birches and other deciduous trees. Crown ratio falls linearly
+    /// with slenderness (100·h/dbh) from the species group's ratio at slenderness 80 and is
+    /// kept between 0.15 and 0.95. Without a diameter the tree is assumed to have the
+    /// reference slenderness. The coefficients are placeholders, not a published model.
+    pub fn of_tree(species: TreeSpecies, diameter: f32, height: f32) -> Crown {
+        let coefficients = coefficients(species);
+        let diameter = if diameter > 0.0 { diameter } else { height * 100.0 / REFERENCE_SLENDERNESS };
+
+        let width = coefficients.width_intercept + coefficients.width_slope * diameter;
+        let slenderness = if diameter > 0.0 { height * 100.0 / diameter } else { REFERENCE_SLENDERNESS };
+        let crown_ratio = (coefficients.crown_ratio - CROWN_RATIO_SLOPE * (slenderness - REFERENCE_SLENDERNESS))
+            .clamp(MIN_CROWN_RATIO, MAX_CROWN_RATIO);
+
+        Crown {
+            radius: width / 2.0,
+            base_height: height * (1.0 - crown_ratio),
+            shape: coefficients.shape,
+        }
+    }
+}
+
+#[test]
+fn test_crown_models() {
+    let pine = Crown::of_tree(TreeSpecies::ScotsPine, 20.0, 16.0);
+    assert_eq!(pine.shape, CrownShape::Ovoid);
+    assert!((pine.radius - 1.525).abs() < 1e-4);
+    assert!((pine.base_height - 16.0 * 0.55).abs() < 1e-4);
+
+    // Slender trees have shorter crowns
+    let spruce = Crown::of_tree(TreeSpecies::NorwaySpruce, 20.0, 16.0);
+    let slender_spruce = Crown::of_tree(TreeSpecies::NorwaySpruce, 15.0, 16.0);
+    assert_eq!(spruce.shape, CrownShape::Conical);
+    assert!(slender_spruce.base_height > spruce.base_height);
+    assert_eq!(Crown::of_tree(TreeSpecies::Aspen, 0.0, 12.0).shape, CrownShape::Spherical);
+}
@@ -0,0 +1,15 @@
+// Quotes a CSV field that contains a separator, a quote or a line break
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[test]
+fn test_csv_field() {
+    assert_eq!(csv_field("12"), "12");
+    assert_eq!(csv_field("12,a"), "\"12,a\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
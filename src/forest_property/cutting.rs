@@ -0,0 +1,301 @@
+use std::cmp::Ordering;
+use geojson::GeoJson;
+use geo::Polygon;
+use crate::geojson_utils::polygons_to_geojson;
+use super::biomass::stratum_biomass;
+use super::codes::{OperationType, Storey};
+use super::compartment::{stand_stratum_trees, Compartment, GenerationOptions};
+use super::forest_property_data::Operation;
+use super::stand::Stand;
+use super::tree::Tree;
+use super::tree_stand_data::{TreeStrata, TreeStratum};
+
+// Stems per hectare left standing in seed tree and shelterwood cuttings
+const SEED_TREES_PER_HECTARE: u32 = 50;
+const SHELTER_TREES_PER_HECTARE: u32 = 200;
+
+/// What a cutting removes. Basal areas are m²/ha, volumes m³/ha and stem counts per hectare,
+/// like the strata they are applied to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RemovalSpec {
+    ClearCut,
+    /// Removes the thinnest strata first until the basal area is down to the target
+    ThinFromBelow { basal_area: f32 },
+    /// Removes the thickest strata first until the basal area is down to the target
+    ThinFromAbove { basal_area: f32 },
+    /// Removes the same share of every stratum
+    RemoveVolume { volume: f32 },
+    /// Leaves the given number of the thickest stems, e.g. seed trees
+    RetainLargest { stem_count: u32 },
+    RemoveStorey(Storey),
+}
+
+impl RemovalSpec {
+    /// Removal of a proposed operation. Operations that are not cuttings give None,
+    /// as do thinnings without a cutting volume.
+    pub fn from_operation(operation: &Operation, stand_area: f32) -> Option<RemovalSpec> {
+        if !operation.is_cutting() {
+            return None;
+        }
+
+        match operation.operation_type {
+            OperationType::ClearCutting => Some(RemovalSpec::ClearCut),
+            OperationType::SeedTreeCutting => Some(RemovalSpec::RetainLargest { stem_count: SEED_TREES_PER_HECTARE }),
+            OperationType::ShelterwoodCutting => Some(RemovalSpec::RetainLargest { stem_count: SHELTER_TREES_PER_HECTARE }),
+            OperationType::OverstoreyRemoval => Some(RemovalSpec::RemoveStorey(Storey::Overstorey)),
+            _ => {
+                // Cutting volume is given for the whole stand
+                let volume = operation.cutting.as_ref()?.cutting_volume;
+                if volume > 0.0 && stand_area > 0.0 {
+                    Some(RemovalSpec::RemoveVolume { volume: volume / stand_area })
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Strata after a cutting and the part of them that was removed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CuttingResult {
+    pub remaining: TreeStrata,
+    pub removed: TreeStrata,
+}
+
+impl CuttingResult {
+    pub fn removed_volume(&self) -> f32 {
+        self.removed.tree_stratum.iter().map(|stratum| stratum.volume).sum()
+    }
+}
+
+// Share `fraction` of the stratum. Mean height and diameter stay the same.
+fn scale_stratum(stratum: &TreeStratum, fraction: f32) -> TreeStratum {
+    TreeStratum {
+        stem_count: (stratum.stem_count as f32 * fraction).round() as u32,
+        basal_area: stratum.basal_area * fraction,
+        volume: stratum.volume * fraction,
+        saw_log_volume: stratum.saw_log_volume * fraction,
+        pulp_wood_volume: stratum.pulp_wood_volume * fraction,
+        volume_growth: stratum.volume_growth * fraction,
+        ..*stratum
+    }
+}
+
+// Share of each stratum to remove
+fn removal_fractions(strata: &[TreeStratum], spec: &RemovalSpec) -> Vec<f32> {
+    match *spec {
+        RemovalSpec::ClearCut => vec![1.0; strata.len()],
+        RemovalSpec::RemoveVolume { volume } => {
+            let total: f32 = strata.iter().map(|stratum| stratum.volume).sum();
+            let fraction = if total > 0.0 { (volume / total).clamp(0.0, 1.0) } else { 0.0 };
+            vec![fraction; strata.len()]
+        }
+        RemovalSpec::RemoveStorey(storey) => strata
+            .iter()
+            .map(|stratum| if stratum.storey == storey { 1.0 } else { 0.0 })
+            .collect(),
+        RemovalSpec::ThinFromBelow { basal_area } | RemovalSpec::ThinFromAbove { basal_area } => {
+            let total: f32 = strata.iter().map(|stratum| stratum.basal_area).sum();
+            let from_below = matches!(spec, RemovalSpec::ThinFromBelow { .. });
+            remove_in_order(strata, from_below, (total - basal_area).max(0.0), |stratum| stratum.basal_area)
+        }
+        RemovalSpec::RetainLargest { stem_count } => {
+            let total: u32 = strata.iter().map(|stratum| stratum.stem_count).sum();
+            let excess = total.saturating_sub(stem_count) as f32;
+            remove_in_order(strata, true, excess, |stratum| stratum.stem_count as f32)
+        }
+    }
+}
+
+// Removes `amount` of `measure` going through the strata by mean diameter
+fn remove_in_order(strata: &[TreeStratum], from_below: bool, mut amount: f32, measure: fn(&TreeStratum) -> f32) -> Vec<f32> {
+    let mut order: Vec<usize> = (0..strata.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ordering = strata[a].mean_diameter.partial_cmp(&strata[b].mean_diameter).unwrap_or(Ordering::Equal);
+        if from_below { ordering } else { ordering.reverse() }
+    });
+
+    let mut fractions = vec![0.0; strata.len()];
+    for i in order {
+        let value = measure(&strata[i]);
+        if amount <= 0.0 {
+            break;
+        }
+        if value <= 0.0 {
+            continue;
+        }
+        fractions[i] = (amount / value).min(1.0);
+        amount -= value * fractions[i];
+    }
+    fractions
+}
+
+/// Applies a removal to strata. Strata that are removed completely are left out of `remaining`.
+pub fn cut_strata(strata: &TreeStrata, spec: &RemovalSpec) -> CuttingResult {
+    cut_by_fractions(strata, &removal_fractions(&strata.tree_stratum, spec))
+}
+
+fn cut_by_fractions(strata: &TreeStrata, fractions: &[f32]) -> CuttingResult {
+    let mut result = CuttingResult::default();
+
+    for (stratum, &fraction) in strata.tree_stratum.iter().zip(fractions) {
+        if fraction > 0.0 {
+            result.removed.tree_stratum.push(scale_stratum(stratum, fraction));
+        }
+        if fraction < 1.0 {
+            result.remaining.tree_stratum.push(scale_stratum(stratum, 1.0 - fraction));
+        }
+    }
+
+    result
+}
+
+// Trees left after removing `fraction` of the trees of a stratum. Thinning from below and retaining
+// the largest stems take the thinnest trees first and thinning from above the thickest. Other
+// removals take the trees in the order they were generated, which is spatially random.
+fn remaining_trees(trees: &[Tree], fraction: f32, spec: &RemovalSpec) -> Vec<Tree> {
+    let count = ((trees.len() as f32 * fraction).round() as usize).min(trees.len());
+    let mut order: Vec<usize> = (0..trees.len()).collect();
+    match spec {
+        RemovalSpec::ThinFromBelow { .. } | RemovalSpec::RetainLargest { .. } => {
+            order.sort_by(|&a, &b| trees[a].diameter().total_cmp(&trees[b].diameter()))
+        }
+        RemovalSpec::ThinFromAbove { .. } => order.sort_by(|&a, &b| trees[b].diameter().total_cmp(&trees[a].diameter())),
+        _ => {}
+    }
+
+    let mut removed = vec![false; trees.len()];
+    for &i in &order[..count] {
+        removed[i] = true;
+    }
+    trees.iter().zip(removed).filter(|(_, removed)| !removed).map(|(tree, _)| *tree).collect()
+}
+
+/// Trees of a stand before and after a cutting
+#[derive(Debug, Clone)]
+pub struct CuttingSimulation {
+    pub result: CuttingResult,
+    pub before: Compartment,
+    pub after: Compartment,
+    /// Every part of the stand. The compartments have the largest part and the trees of all parts.
+    pub polygons: Vec<Polygon>,
+}
+
+impl CuttingSimulation {
+    pub fn before_to_geojson(&self) -> GeoJson {
+        polygons_to_geojson(&self.polygons, &self.before.trees)
+    }
+
+    pub fn after_to_geojson(&self) -> GeoJson {
+        polygons_to_geojson(&self.polygons, &self.after.trees)
+    }
+}
+
+impl Stand {
+    /// Cuts the strata of the selected snapshot. Stands whose special features forbid
+    /// removal are left as they are.
+    pub fn cut(&self, spec: &RemovalSpec, options: &GenerationOptions) -> Option<CuttingResult> {
+        let strata = self.get_strata_for(&options.snapshot)?;
+        Some(cut_by_fractions(&strata, &self.removal_fractions(&strata, spec, options)))
+    }
+
+    fn removal_fractions(&self, strata: &TreeStrata, spec: &RemovalSpec, options: &GenerationOptions) -> Vec<f32> {
+        if options.feature_rules.zone(self).no_removal {
+            return vec![0.0; strata.tree_stratum.len()];
+        }
+        removal_fractions(&strata.tree_stratum, spec)
+    }
+
+    /// Cuts the strata of the snapshot selected in `options` and generates the trees
+    /// of the whole stand before the cutting. The trees after it are the ones of each
+    /// stratum that the cutting leaves standing.
+    pub fn simulate_cutting(&self, spec: &RemovalSpec, options: &GenerationOptions) -> Option<CuttingSimulation> {
+        let polygon = self.computed_polygon.to_owned()?;
+        let strata = self.get_strata_for(&options.snapshot)?;
+        let fractions = self.removal_fractions(&strata, spec, options);
+        let result = cut_by_fractions(&strata, &fractions);
+
+        let (stratum_trees, _) = stand_stratum_trees(self, &strata, options);
+        let after_trees = stratum_trees
+            .iter()
+            .zip(&fractions)
+            .flat_map(|(trees, &fraction)| remaining_trees(trees, fraction, spec))
+            .collect();
+
+        let compartment = |strata: &TreeStrata, trees: Vec<Tree>| {
+            let mut compartment = Compartment::new(self.stand_basic_data.stand_number.to_string(), trees, polygon.to_owned());
+            compartment.real_estate_id = self.real_estate_id;
+            compartment.parcel_number = self.parcel_number;
+            compartment.special_feature_codes = self.special_features().iter().map(|feature| feature.feature_code.trim().to_string()).collect();
+            compartment.biomass = strata.tree_stratum
+                .iter()
+                .map(|stratum| stratum_biomass(stratum, self.stand_basic_data.area as f64))
+                .sum();
+            compartment
+        };
+
+        Some(CuttingSimulation {
+            before: compartment(&strata, stratum_trees.into_iter().flatten().collect()),
+            after: compartment(&result.remaining, after_trees),
+            result,
+            polygons: self.polygons().to_vec(),
+        })
+    }
+}
+
+#[test]
+fn test_cut_strata() {
+    let stratum = |diameter: f32, basal_area: f32, stem_count: u32| TreeStratum {
+        mean_diameter: diameter,
+        basal_area,
+        stem_count,
+        volume: basal_area * 5.0,
+        ..Default::default()
+    };
+    let strata = TreeStrata::new(vec![stratum(8.0, 4.0, 800), stratum(20.0, 10.0, 300), stratum(30.0, 10.0, 140)]);
+    let basal_area = |strata: &TreeStrata| -> f32 { strata.tree_stratum.iter().map(|stratum| stratum.basal_area).sum() };
+
+    // Thinning from below takes the thinnest stratum and part of the next one
+    let result = cut_strata(&strata, &RemovalSpec::ThinFromBelow { basal_area: 18.0 });
+    assert!((basal_area(&result.remaining) - 18.0).abs() < 1e-4);
+    assert_eq!(result.remaining.tree_stratum.len(), 2);
+    assert_eq!(result.remaining.tree_stratum[0].mean_diameter, 20.0);
+
+    let result = cut_strata(&strata, &RemovalSpec::ThinFromAbove { basal_area: 18.0 });
+    assert!((result.remaining.tree_stratum[2].basal_area - 4.0).abs() < 1e-4);
+
+    let result = cut_strata(&strata, &RemovalSpec::RetainLargest { stem_count: 50 });
+    assert_eq!(result.remaining.tree_stratum.len(), 1);
+    assert_eq!(result.remaining.tree_stratum[0].stem_count, 50);
+
+    let result = cut_strata(&strata, &RemovalSpec::ClearCut);
+    assert!(result.remaining.tree_stratum.is_empty());
+    assert_eq!(result.removed_volume(), 120.0);
+}
+
+#[test]
+fn test_simulated_cutting_keeps_standing_trees() {
+    use super::forest_property_data::ForestPropertyData;
+
+    let stands = ForestPropertyData::from_xml_file("forestpropertydata.xml").get_stands();
+    let stand = stands.iter()
+        .filter(|stand| stand.computed_polygon.is_some() && stand.get_strata().is_some_and(|strata| strata.tree_stratum.iter().all(|stratum| stratum.stem_count > 0)))
+        .min_by(|a, b| a.stand_basic_data.area.total_cmp(&b.stand_basic_data.area))
+        .unwrap();
+    let basal_area: f32 = stand.get_strata().unwrap().tree_stratum.iter().map(|stratum| stratum.basal_area).sum();
+
+    let spec = RemovalSpec::ThinFromBelow { basal_area: basal_area / 2.0 };
+    let simulation = stand.simulate_cutting(&spec, &GenerationOptions::default()).unwrap();
+    let before: Vec<(f64, f64, f64)> = simulation.before.trees.iter().map(|tree| tree.position()).collect();
+    assert!(simulation.after.trees.len() < simulation.before.trees.len());
+    assert!(simulation.after.trees.iter().all(|tree| before.contains(&tree.position())));
+
+    // Within a stratum the thinnest trees are removed
+    for after in &simulation.after.trees {
+        let removed = simulation.before.trees.iter()
+            .filter(|tree| tree.species() == after.species() && tree.mean_height() == after.mean_height())
+            .filter(|tree| !simulation.after.trees.iter().any(|kept| kept.position() == tree.position()));
+        assert!(removed.into_iter().all(|tree| tree.diameter() <= after.diameter()));
+    }
+}
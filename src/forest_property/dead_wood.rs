@@ -0,0 +1,263 @@
+use std::f64::consts::PI;
+use geo::{BoundingRect, Contains, Coord, Line, Point, Polygon};
+use rand::Rng;
+use serde::Serialize;
+use crate::geometry_utils::METRES_PER_DEGREE;
+use super::codes::{DeadTreeType, TreeSpecies};
+use super::diameter_distribution::basal_area;
+use super::tree_stand_data::DeadTreeStratum;
+
+// Used when a stratum has a volume but no mean diameter, cm
+const DEFAULT_DIAMETER: f32 = 20.0;
+// Length of a dead stem in metres per cm of diameter, and the limits of the length
+const LENGTH_PER_DIAMETER: f32 = 0.8;
+const MIN_LENGTH: f32 = 2.0;
+const MAX_LENGTH: f32 = 30.0;
+// Stem volume relative to a cylinder of the breast height diameter
+const FORM_FACTOR: f32 = 0.5;
+const MAX_POSITION_ATTEMPTS: usize = 1000;
+
+/// Snags are points, fallen logs lines from the root end to the top
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeadWoodGeometry {
+    Standing(Point<f64>),
+    Fallen(Line<f64>),
+}
+
+/// A standing snag or a fallen log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeadTree {
+    pub dead_tree_type: DeadTreeType,
+    pub species: TreeSpecies,
+    /// cm
+    pub diameter: f32,
+    /// Height of a snag or length of a log in metres
+    pub length: f32,
+    /// m³
+    pub volume: f32,
+    pub geometry: DeadWoodGeometry,
+}
+
+impl DeadTree {
+    pub fn is_fallen(&self) -> bool {
+        matches!(self.geometry, DeadWoodGeometry::Fallen(_))
+    }
+}
+
+fn stem_length(diameter: f32) -> f32 {
+    (diameter * LENGTH_PER_DIAMETER).clamp(MIN_LENGTH, MAX_LENGTH)
+}
+
+fn stem_volume(diameter: f32, length: f32) -> f32 {
+    FORM_FACTOR * basal_area(diameter as f64) as f32 * length
+}
+
+// Dead trees standing inside the polygon. Fallen logs are kept if their root end is inside.
+pub fn clip_dead_trees(dead_trees: &[DeadTree], polygon: &Polygon) -> Vec<DeadTree> {
+    dead_trees
+        .iter()
+        .filter(|dead_tree| match dead_tree.geometry {
+            DeadWoodGeometry::Standing(point) => polygon.contains(&point),
+            DeadWoodGeometry::Fallen(line) => polygon.contains(&line.start),
+        })
+        .copied()
+        .collect()
+}
+
+// Random point inside the polygon, None if none is found, e.g. for a degenerate polygon
+fn random_point<R: Rng>(polygon: &Polygon, rng: &mut R) -> Option<Point<f64>> {
+    let rect = polygon.bounding_rect()?;
+    (0..MAX_POSITION_ATTEMPTS)
+        .map(|_| Point::new(rng.gen_range(rect.min().x..=rect.max().x), rng.gen_range(rect.min().y..=rect.max().y)))
+        .find(|point| polygon.contains(point))
+}
+
+// Log of `length` metres lying from `root` in a random direction. Coordinates are WGS84 degrees.
+fn random_log<R: Rng>(root: Point<f64>, length: f32, rng: &mut R) -> Line<f64> {
+    let angle = rng.gen_range(0.0..2.0 * PI);
+    let dx = length as f64 * angle.cos() / (METRES_PER_DEGREE * root.y().to_radians().cos());
+    let dy = length as f64 * angle.sin() / METRES_PER_DEGREE;
+
+    Line::new(root.0, Coord { x: root.x() + dx, y: root.y() + dy })
+}
+
+/// Target and generated dead trees of one stratum
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct DeadWoodFill {
+    /// Index of the stratum in its strata
+    pub index: usize,
+    pub dead_tree_type: DeadTreeType,
+    pub tree_species: TreeSpecies,
+    pub target: usize,
+    pub achieved: usize,
+    /// Volume of the stratum and of the generated trees, m³
+    pub target_volume: f32,
+    pub volume: f32,
+}
+
+impl DeadWoodFill {
+    pub fn is_complete(&self) -> bool {
+        self.achieved >= self.target
+    }
+}
+
+/// Target and generated dead trees of the dead tree strata of a stand
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct DeadWoodReport {
+    pub strata: Vec<DeadWoodFill>,
+}
+
+impl DeadWoodReport {
+    pub fn is_complete(&self) -> bool {
+        self.strata.iter().all(|stratum| stratum.is_complete())
+    }
+
+    // Strata that got fewer dead trees than their target, e.g. when no position was found
+    pub fn incomplete(&self) -> impl Iterator<Item = &DeadWoodFill> {
+        self.strata.iter().filter(|stratum| !stratum.is_complete())
+    }
+
+    // Adds the report of another part of the same stand, strata with the same index are summed
+    pub fn merge(&mut self, other: DeadWoodReport) {
+        for stratum in other.strata {
+            match self.strata.iter_mut().find(|existing| existing.index == stratum.index) {
+                Some(existing) => {
+                    existing.target += stratum.target;
+                    existing.achieved += stratum.achieved;
+                    existing.target_volume += stratum.target_volume;
+                    existing.volume += stratum.volume;
+                }
+                None => self.strata.push(stratum),
+            }
+        }
+    }
+}
+
+/// Generates the dead trees of the strata inside a polygon in WGS84 coordinates.
+/// Stratum volumes are m³/ha and `area` is the area of the polygon in hectares.
+/// All trees of a stratum get its mean diameter, and their lengths are scaled so that
+/// the volumes add up to the stratum's volume.
+/// If no position is found for a tree, the rest of its stratum is left out and the
+/// shortfall is shown in the report.
+pub fn generate_dead_wood<R: Rng>(
+    polygon: &Polygon,
+    strata: &[DeadTreeStratum],
+    area: f64,
+    rng: &mut R
+) -> (Vec<DeadTree>, DeadWoodReport) {
+    let mut dead_trees = Vec::new();
+    let mut report = DeadWoodReport::default();
+
+    for (index, stratum) in strata.iter().enumerate() {
+        let total_volume = stratum.volume as f64 * area;
+        if total_volume <= 0.0 || !stratum.dead_tree_type.is_known() {
+            continue;
+        }
+
+        let diameter = if stratum.mean_diameter > 0.0 { stratum.mean_diameter } else { DEFAULT_DIAMETER };
+        let length = stem_length(diameter);
+        let count = (total_volume / stem_volume(diameter, length) as f64).round().max(1.0) as usize;
+        let volume = (total_volume / count as f64) as f32;
+        let length = length * volume / stem_volume(diameter, length);
+
+        let mut fill = DeadWoodFill {
+            index,
+            dead_tree_type: stratum.dead_tree_type,
+            tree_species: stratum.tree_species,
+            target: count,
+            achieved: 0,
+            target_volume: total_volume as f32,
+            volume: 0.0,
+        };
+
+        for _ in 0..count {
+            let Some(position) = random_point(polygon, rng) else {
+                break;
+            };
+            let geometry = match stratum.dead_tree_type {
+                DeadTreeType::Fallen => DeadWoodGeometry::Fallen(random_log(position, length, rng)),
+                _ => DeadWoodGeometry::Standing(position),
+            };
+
+            dead_trees.push(DeadTree {
+                dead_tree_type: stratum.dead_tree_type,
+                species: stratum.tree_species,
+                diameter,
+                length,
+                volume,
+                geometry,
+            });
+            fill.achieved += 1;
+            fill.volume += volume;
+        }
+
+        report.strata.push(fill);
+    }
+
+    (dead_trees, report)
+}
+
+#[test]
+fn test_generate_dead_wood() {
+    use geo::{LineString, GeodesicLength};
+    use rand::SeedableRng;
+
+    let polygon = Polygon::new(
+        LineString::from(vec![(25.0, 65.0), (25.01, 65.0), (25.01, 65.01), (25.0, 65.01), (25.0, 65.0)]),
+        vec![],
+    );
+    let stratum = |dead_tree_type: DeadTreeType, volume: f32| DeadTreeStratum {
+        id: 0,
+        change_state: None,
+        dead_tree_type,
+        tree_species: TreeSpecies::NorwaySpruce,
+        volume,
+        mean_diameter: 25.0,
+    };
+    let strata = vec![stratum(DeadTreeType::Standing, 4.0), stratum(DeadTreeType::Fallen, 6.0), stratum(DeadTreeType::Kelo, 0.0)];
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let (dead_trees, report) = generate_dead_wood(&polygon, &strata, 2.0, &mut rng);
+    assert!(report.is_complete());
+    assert_eq!(report.strata.iter().map(|stratum| stratum.index).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(report.strata.iter().map(|stratum| stratum.achieved).sum::<usize>(), dead_trees.len());
+
+    let volume = |fallen: bool| -> f32 {
+        dead_trees.iter().filter(|tree| tree.is_fallen() == fallen).map(|tree| tree.volume).sum()
+    };
+    assert!((volume(false) - 8.0).abs() < 1e-3);
+    assert!((volume(true) - 12.0).abs() < 1e-3);
+
+    for tree in &dead_trees {
+        match tree.geometry {
+            DeadWoodGeometry::Standing(point) => assert!(polygon.contains(&point)),
+            DeadWoodGeometry::Fallen(line) => {
+                assert!(polygon.contains(&Point(line.start)));
+                assert!((line.geodesic_length() - tree.length as f64).abs() < 0.1 * tree.length as f64);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generate_dead_wood_without_positions() {
+    use geo::LineString;
+    use rand::SeedableRng;
+
+    // A polygon without area has no positions, every stratum is reported short
+    let polygon = Polygon::new(LineString::from(vec![(25.0, 65.0), (25.01, 65.0), (25.0, 65.0)]), vec![]);
+    let stratum = |dead_tree_type: DeadTreeType| DeadTreeStratum {
+        id: 0,
+        change_state: None,
+        dead_tree_type,
+        tree_species: TreeSpecies::NorwaySpruce,
+        volume: 4.0,
+        mean_diameter: 25.0,
+    };
+    let strata = vec![stratum(DeadTreeType::Standing), stratum(DeadTreeType::Fallen)];
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let (dead_trees, report) = generate_dead_wood(&polygon, &strata, 2.0, &mut rng);
+
+    assert!(dead_trees.is_empty());
+    assert_eq!(report.incomplete().count(), 2);
+    assert!(report.strata.iter().all(|stratum| stratum.target > 0 && stratum.achieved == 0 && stratum.target_volume > 0.0));
+}
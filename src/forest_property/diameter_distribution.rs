@@ -0,0 +1,214 @@
+use std::f64::consts::PI;
+use rand::Rng;
+use super::tree::Tree;
+use super::tree_stand_data::TreeStratum;
+
+// Shape of the distribution when the stratum doesn't have the data to fit it
+const DEFAULT_SHAPE: f64 = 3.6;
+const MIN_SHAPE: f64 = 1.2;
+const MAX_SHAPE: f64 = 30.0;
+// Height of the Näslund curve's asymptote relative to the mean height above breast height
+const ASYMPTOTE_RATIO: f64 = 1.5;
+const BREAST_HEIGHT: f64 = 1.3;
+
+/// Basal area of a tree in m² from its diameter at breast height in cm
+pub fn basal_area(diameter: f64) -> f64 {
+    PI * diameter * diameter / 40_000.0
+}
+
+/// Weibull distribution of breast height diameters (cm)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeibullDiameter {
+    pub shape: f64,
+    pub scale: f64,
+}
+
+impl WeibullDiameter {
+    /// Fits the distribution to a stratum. The scale comes from the quadratic mean diameter
+    /// given by `BasalArea` and `StemCount`, the shape from the ratio of the basal area weighted
+    /// `MeanDiameter` to it. Without `BasalArea` or `StemCount` the shape is `DEFAULT_SHAPE`
+    /// and the scale comes from `MeanDiameter`. Returns None if the stratum has no diameter information.
+    pub fn from_stratum(stratum: &TreeStratum) -> Option<Self> {
+        let mean_diameter = stratum.mean_diameter as f64;
+        if stratum.stem_count == 0 || stratum.basal_area <= 0.0 {
+            if mean_diameter <= 0.0 {
+                return None;
+            }
+            // Basal area weighted mean is scale · Γ(1 + 3/k) / Γ(1 + 2/k)
+            let scale = mean_diameter * gamma(1.0 + 2.0 / DEFAULT_SHAPE) / gamma(1.0 + 3.0 / DEFAULT_SHAPE);
+            return Some(WeibullDiameter { shape: DEFAULT_SHAPE, scale });
+        }
+        let quadratic_mean = (stratum.basal_area as f64 / stratum.stem_count as f64 / basal_area(1.0)).sqrt();
+
+        let shape = if mean_diameter > 0.0 {
+            fit_shape(mean_diameter / quadratic_mean)
+        } else {
+            DEFAULT_SHAPE
+        };
+        let scale = quadratic_mean / gamma(1.0 + 2.0 / shape).sqrt();
+
+        Some(WeibullDiameter { shape, scale })
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        let u: f64 = rng.gen();
+        self.scale * (-(1.0 - u).ln()).powf(1.0 / self.shape)
+    }
+
+    // Basal area weighted mean diameter, E[D³] / E[D²]
+    fn weighted_mean_ratio(shape: f64) -> f64 {
+        gamma(1.0 + 3.0 / shape) / gamma(1.0 + 2.0 / shape).powf(1.5)
+    }
+}
+
+// Shape whose basal area weighted mean to quadratic mean ratio is `ratio`. The ratio falls
+// towards 1 as the shape grows, ratios outside the searched range get the nearest end.
+fn fit_shape(ratio: f64) -> f64 {
+    if !ratio.is_finite() || ratio <= 1.0 {
+        return MAX_SHAPE;
+    }
+    let (mut low, mut high) = (MIN_SHAPE, MAX_SHAPE);
+    if ratio >= WeibullDiameter::weighted_mean_ratio(low) {
+        return low;
+    }
+    for _ in 0..60 {
+        let middle = (low + high) / 2.0;
+        if WeibullDiameter::weighted_mean_ratio(middle) > ratio {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+// Lanczos approximation of the gamma function for positive arguments
+fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        return PI / ((PI * x).sin() * gamma(1.0 - x));
+    }
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, coefficient)| sum + coefficient / (x + i as f64 + 1.0));
+
+    (2.0 * PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum
+}
+
+/// Näslund height curve h = 1.3 + d² / (a + b·d)², calibrated so that a tree of the
+/// stratum's mean diameter gets the stratum's mean height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NaslundCurve {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl NaslundCurve {
+    pub fn calibrate(mean_diameter: f64, mean_height: f64) -> Option<Self> {
+        if mean_diameter <= 0.0 || mean_height <= BREAST_HEIGHT {
+            return None;
+        }
+        let height = mean_height - BREAST_HEIGHT;
+        let b = 1.0 / (ASYMPTOTE_RATIO * height).sqrt();
+        let a = mean_diameter / height.sqrt() - b * mean_diameter;
+
+        Some(NaslundCurve { a, b })
+    }
+
+    pub fn height(&self, diameter: f64) -> f64 {
+        if diameter <= 0.0 {
+            return BREAST_HEIGHT;
+        }
+        BREAST_HEIGHT + (diameter / (self.a + self.b * diameter)).powi(2)
+    }
+}
+
+/// Samples diameters for `count` trees of the stratum. The diameters are scaled so that
+/// the trees have the stratum's basal area per stem on average.
+pub fn sample_diameters<R: Rng>(stratum: &TreeStratum, count: usize, rng: &mut R) -> Vec<f64> {
+    let Some(distribution) = WeibullDiameter::from_stratum(stratum) else {
+        return vec![0.0; count];
+    };
+    let mut diameters: Vec<f64> = (0..count).map(|_| distribution.sample(rng)).collect();
+
+    if stratum.stem_count > 0 && stratum.basal_area > 0.0 {
+        let target = count as f64 * stratum.basal_area as f64 / stratum.stem_count as f64;
+        let sampled: f64 = diameters.iter().map(|diameter| basal_area(*diameter)).sum();
+        if sampled > 0.0 {
+            let factor = (target / sampled).sqrt();
+            diameters.iter_mut().for_each(|diameter| *diameter *= factor);
+        }
+    }
+
+    diameters
+}
+
+/// Trees of a stratum at the given positions, with sampled diameters and heights
+/// from the stratum's height curve.
+pub fn stratum_trees<R: Rng>(stratum: &TreeStratum, positions: &[[f64; 2]], rng: &mut R) -> Vec<Tree> {
+    let diameters = sample_diameters(stratum, positions.len(), rng);
+    let curve = NaslundCurve::calibrate(stratum.mean_diameter as f64, stratum.mean_height as f64);
+
+    positions
+        .iter()
+        .zip(diameters)
+        .map(|(position, diameter)| {
+            let height = match curve {
+                Some(curve) => curve.height(diameter) as f32,
+                None => stratum.mean_height,
+            };
+            Tree::new(stratum.tree_species, stratum.mean_height, (position[0], position[1], 0.0))
+                .with_dimensions(diameter as f32, height)
+        })
+        .collect()
+}
+
+#[test]
+fn test_stratum_tree_dimensions() {
+    use rand::SeedableRng;
+
+    assert!((gamma(5.0) - 24.0).abs() < 1e-9);
+
+    let stratum = TreeStratum { stem_count: 600, basal_area: 18.0, mean_diameter: 21.0, mean_height: 18.0, ..Default::default() };
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let positions = vec![[0.0, 0.0]; 600];
+    let trees = stratum_trees(&stratum, &positions, &mut rng);
+
+    let total_basal_area: f64 = trees.iter().map(|tree| basal_area(tree.diameter() as f64)).sum();
+    assert!((total_basal_area - 18.0).abs() < 1e-3);
+    assert!(trees.iter().any(|tree| tree.diameter() < 15.0) && trees.iter().any(|tree| tree.diameter() > 25.0));
+
+    let curve = NaslundCurve::calibrate(21.0, 18.0).unwrap();
+    assert!((curve.height(21.0) - 18.0).abs() < 1e-9);
+    assert!(curve.height(10.0) < 18.0 && curve.height(30.0) > 18.0);
+}
+
+#[test]
+fn test_diameters_without_basal_area() {
+    use rand::SeedableRng;
+
+    let stratum = TreeStratum { mean_diameter: 21.0, mean_height: 18.0, ..Default::default() };
+    let distribution = WeibullDiameter::from_stratum(&stratum).unwrap();
+    assert_eq!(distribution.shape, DEFAULT_SHAPE);
+    let weighted_mean = distribution.scale * gamma(1.0 + 3.0 / DEFAULT_SHAPE) / gamma(1.0 + 2.0 / DEFAULT_SHAPE);
+    assert!((weighted_mean - 21.0).abs() < 1e-9);
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let diameters = sample_diameters(&stratum, 600, &mut rng);
+    assert!(diameters.iter().any(|diameter| *diameter < 12.0) && diameters.iter().any(|diameter| *diameter > 25.0));
+}
@@ -1,12 +1,25 @@
-use std::fs::{self, File};
+use std::fs;
+use chrono::NaiveDate;
+#[cfg(test)]
+use std::fs::File;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
+use crate::error::ForestDataError;
+use crate::geometry_utils::bounding_box_of_polygons;
 use super::{geometry::PolygonGeometry, stand::{Stand, Stands}};
+use super::codes::{deserialize_optional_code, ChangeState, DeadTreeType, DevelopmentClass, DrainageState, FertilityClass, MainGroup, OperationType, SoilType, Storey, TreeSpecies, TreeStandDataType};
+use super::schema_version::SchemaVersion;
+use super::xml_reader::{decode_xml_bytes, locate_schema_error};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ForestPropertyData {
-    #[serde(rename = "RealEstates")]
+    #[serde(rename = "RealEstates", default)]
     pub real_estates: RealEstates,
+    // Newer revisions allow stands directly under the root, without a real estate
+    #[serde(rename = "Stands", skip_serializing_if = "Option::is_none")]
+    pub stands: Option<Stands>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub schema_version: SchemaVersion,
 }
 
 pub fn read_number_cli(min: usize, max: usize) -> usize {
@@ -27,14 +40,25 @@ pub fn read_number_cli(min: usize, max: usize) -> usize {
 
 impl ForestPropertyData {
     pub fn from_xml_file(path: &str) -> ForestPropertyData {
-        let xml = fs::read_to_string(path).expect("Could not read the XML file");
-        ForestPropertyData::parse_from_str(xml.as_str())
+        ForestPropertyData::try_from_xml_file(path)
+            .unwrap_or_else(|e| panic!("Could not load forest property data: {}", e))
     }
 
     pub fn from_xml_str(xml_str: &str) -> ForestPropertyData {
         ForestPropertyData::parse_from_str(xml_str)
     }
 
+    // Reads the file and decodes it with the encoding declared in the XML declaration
+    pub fn try_from_xml_file(path: &str) -> Result<ForestPropertyData, ForestDataError> {
+        let bytes = fs::read(path)?;
+        let xml = decode_xml_bytes(&bytes)?;
+        ForestPropertyData::try_parse_from_str(&xml)
+    }
+
+    pub fn try_from_xml_str(xml_str: &str) -> Result<ForestPropertyData, ForestDataError> {
+        ForestPropertyData::try_parse_from_str(xml_str)
+    }
+
     #[cfg(test)]
     pub fn from_json_file(path: &str) -> ForestPropertyData {
         let json = fs::read_to_string(path).expect("Could not read the JSON file");
@@ -55,21 +79,71 @@ impl ForestPropertyData {
     }
 
     pub fn parse_from_str(xml: &str) -> ForestPropertyData {
-        quick_xml::de::from_str(xml).expect("Could not parse the XML")
+        ForestPropertyData::try_parse_from_str(xml).expect("Could not parse the XML")
+    }
+
+    // On failure the document is scanned again to find the element that could not be parsed
+    pub fn try_parse_from_str(xml: &str) -> Result<ForestPropertyData, ForestDataError> {
+        let schema_version = SchemaVersion::detect(xml);
+        if !schema_version.is_supported() {
+            return Err(ForestDataError::UnsupportedVersion(schema_version));
+        }
+
+        let mut property: ForestPropertyData = quick_xml::de::from_str(xml).map_err(|e| locate_schema_error(xml, e))?;
+        property.schema_version = schema_version;
+        Ok(property)
     }
 
     // Parcels are not probably needed in this context but its good to keep them just in case
     pub fn choose_parcel(&self) -> Parcel {
-        let parcels: &Vec<Parcel> = &self.real_estates.real_estate.first().unwrap().parcels.parcel;
+        let parcels: Vec<(&RealEstate, &Parcel)> = self.real_estates.real_estate.iter()
+            .flat_map(|real_estate| real_estate.parcels.parcel.iter().map(move |parcel| (real_estate, parcel)))
+            .collect();
 
         println!("\nParcels:");
-        for (i, parcel) in parcels.iter().enumerate() {
-            print!("{}. {:?}, ", i, parcel.parcel_number);
+        for (i, (real_estate, parcel)) in parcels.iter().enumerate() {
+            print!("{}. {:?} ({}), ", i, parcel.parcel_number, real_estate.real_estate_name);
         }
         println!("Choose a parcel to view: ");
 
         let parcel_index = read_number_cli(0, parcels.len());
-        parcels[parcel_index].to_owned()
+        parcels[parcel_index].1.to_owned()
+    }
+
+    // Stands of all real estates and parcels of the property
+    pub fn get_stands(&self) -> Vec<Stand> {
+        self.get_stands_filtered(&StandFilter::default())
+    }
+
+    pub fn get_stands_filtered(&self, filter: &StandFilter) -> Vec<Stand> {
+        let mut stands: Vec<Stand> = self.real_estates.real_estate
+            .iter()
+            .filter(|real_estate| filter.real_estate_id.is_none_or(|id| real_estate.id == id))
+            .flat_map(|real_estate| real_estate.get_stands_filtered(filter))
+            .collect();
+
+        // Stands without a real estate only match a filter that doesn't select one
+        if let Some(root_stands) = &self.stands {
+            if filter.real_estate_id.is_none() && filter.parcel_number.is_none() {
+                stands.extend(root_stands.stand.iter().map(|stand| {
+                    let mut stand = stand.to_owned();
+                    stand.compute_polygon();
+                    stand
+                }));
+            }
+        }
+
+        stands
+    }
+
+    pub fn get_real_estate(&self, id: u32) -> Option<&RealEstate> {
+        self.real_estates.real_estate.iter().find(|real_estate| real_estate.id == id)
+    }
+
+    // Bounding box of the stands that pass the filter
+    pub fn get_bounding_box(&self, filter: &StandFilter) -> Option<geo::Polygon<f64>> {
+        let stands = self.get_stands_filtered(filter);
+        bounding_box_of_polygons(stands.iter().flat_map(|stand| stand.polygons()))
     }
 
     pub fn get_stand_cli(&self) -> Stand {
@@ -108,10 +182,10 @@ impl ForestPropertyData {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct RealEstates {
     
-    #[serde(rename = "RealEstate")]
+    #[serde(rename = "RealEstate", default)]
     pub real_estate: Vec<RealEstate>,
 }
 
@@ -136,13 +210,25 @@ pub struct RealEstate {
 impl RealEstate {
     
     pub fn get_stands(&self) -> Vec<Stand> {
+        self.get_stands_filtered(&StandFilter::default())
+    }
+
+    // Stands of the parcels that pass the filter, tagged with the real estate and parcel they belong to
+    pub fn get_stands_filtered(&self, filter: &StandFilter) -> Vec<Stand> {
 
         let parcels = &self.parcels.parcel;
 
         let stands_data: Vec<Stand> = parcels
             .into_par_iter()
+            .filter(|parcel| filter.parcel_number.is_none_or(|number| parcel.parcel_number == number))
             .map(|parcel: &Parcel| {
-                let stands: Vec<Stand> = parcel.stands.stand.iter().map( | f| f.to_owned().compute_polygon().to_owned()).collect();
+                let stands: Vec<Stand> = parcel.stands.stand.iter().map(|f| {
+                    let mut stand = f.to_owned();
+                    stand.real_estate_id = Some(self.id);
+                    stand.parcel_id = Some(parcel.id);
+                    stand.parcel_number = Some(parcel.parcel_number);
+                    stand.compute_polygon().to_owned()
+                }).collect();
                 stands
             }).flatten()
             .collect();
@@ -152,6 +238,13 @@ impl RealEstate {
 
 }
 
+/// Selects stands by real estate id and parcel number. `None` matches everything.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StandFilter {
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Parcels {
     
@@ -173,6 +266,8 @@ pub struct Parcel {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct StandBasicData {
     
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     #[serde(rename = "Identifiers")]
     pub identifiers: Option<Identifiers>,
     #[serde(rename = "CuttingRestriction", default)]
@@ -181,17 +276,17 @@ pub struct StandBasicData {
     pub stand_info: Option<String>,
     #[serde(rename = "DitchingYear")]
     pub ditching_year: Option<u16>,
-    #[serde(rename = "ChangeTime")]
+    #[serde(rename = "ChangeTime", default)]
     pub change_time: String,
-    #[serde(rename = "CompleteState")]
+    #[serde(rename = "CompleteState", default)]
     pub complete_state: u8,
     #[serde(rename = "StandNumber")]
     pub stand_number: u16,
-    #[serde(rename = "StandNumberExtension")]
+    #[serde(rename = "StandNumberExtension", default)]
     pub stand_number_extension: String,
     #[serde(rename = "MainGroup")]
-    pub main_group: u8,
-    #[serde(rename = "StandBasicDataDate")]
+    pub main_group: MainGroup,
+    #[serde(rename = "StandBasicDataDate", default)]
     pub stand_basic_data_date: String,
     #[serde(rename = "Area")]
     pub area: f32,
@@ -202,17 +297,17 @@ pub struct StandBasicData {
     #[serde(rename = "Accessibility")]
     pub accessibility: Option<u8>,
     #[serde(rename = "MainTreeSpecies")]
-    pub main_tree_species: Option<u8>,
+    pub main_tree_species: Option<TreeSpecies>,
     #[serde(rename = "StandQuality")]
     pub stand_quality: Option<u8>,
     #[serde(rename = "DevelopmentClass")]
-    pub development_class: Option<String>,
+    pub development_class: Option<DevelopmentClass>,
     #[serde(rename = "DrainageState")]
-    pub drainage_state: Option<u8>,
+    pub drainage_state: Option<DrainageState>,
     #[serde(rename = "SoilType")]
-    pub soil_type: Option<u8>,
+    pub soil_type: Option<SoilType>,
     #[serde(rename = "FertilityClass")]
-    pub fertility_class: Option<u8>,
+    pub fertility_class: Option<FertilityClass>,
     #[serde(rename = "SubGroup")]
     pub sub_group: Option<u8>,
 }
@@ -233,7 +328,7 @@ pub struct Identifier {
     pub identifier_value: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct SpecialFeatures {
     
     #[serde(rename = "SpecialFeature")]
@@ -244,6 +339,8 @@ pub struct SpecialFeatures {
 pub struct SpecialFeature {
     #[serde(rename = "@id")]
     pub id: u32,
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     
     #[serde(rename = "FeatureAdditionalCode")]
     pub feature_additional_code: Option<String>,
@@ -251,7 +348,7 @@ pub struct SpecialFeature {
     pub feature_code: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Operations {
     
     #[serde(rename = "Operation")]
@@ -272,16 +369,32 @@ pub struct Operation {
     pub specifications: Option<Specifications>,
     #[serde(rename = "Silviculture")]
     pub silviculture: Option<Silviculture>,
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     #[serde(rename = "ChangeTime")]
     pub change_time: String,
     #[serde(rename = "OperationType")]
-    pub operation_type: String,
+    pub operation_type: OperationType,
     #[serde(rename = "ProposalData")]
     pub proposal_data: ProposalData,
     #[serde(rename = "Cutting")]
     pub cutting: Option<Cutting>,
 }
 
+impl Operation {
+    pub fn is_cutting(&self) -> bool {
+        self.operation_type.is_cutting()
+    }
+
+    // Assortments of a cutting, empty for other operations
+    pub fn assortments(&self) -> &[Assortment] {
+        match self.cutting.as_ref().and_then(|cutting| cutting.assortments.as_ref()) {
+            Some(assortments) => &assortments.assortment,
+            None => &[],
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CompletionData {
     
@@ -300,6 +413,8 @@ pub struct Specifications {
 pub struct Specification {
     #[serde(rename = "@id")]
     pub id: u32,
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     #[serde(rename = "SpecificationCode")]
     pub specification_code: u32,
 }
@@ -335,15 +450,17 @@ pub struct Assortments {
 pub struct Assortment {
     #[serde(rename = "@id")]
     pub id: u32,
-    #[serde(rename = "TreeSpecies")]
-    pub tree_species: Option<String>, // This fails to parse even with default values unless type is Option<String>. Requires manual parsing.
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
+    #[serde(rename = "TreeSpecies", default, deserialize_with = "deserialize_optional_code")]
+    pub tree_species: Option<TreeSpecies>,
     #[serde(rename = "StemType", default = "default_zero_u32")]
     pub stem_type: u32,
     #[serde(rename = "AssortmentVolume", default = "default_zero_f32")]
     pub assortment_volume: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct TreeStandData {
     
     #[serde(rename = "TreeStandDataDate")]
@@ -355,7 +472,7 @@ pub struct TreeStandDataDate {
     #[serde(rename = "@date", default)]
     pub date: String,
     #[serde(rename = "@type", default)]
-    pub tree_stand_data_date_type: u8,
+    pub tree_stand_data_date_type: TreeStandDataType,
     
     #[serde(rename = "DeadTreeStrata")]
     pub dead_tree_strata: Option<DeadTreeStrata>,
@@ -365,7 +482,53 @@ pub struct TreeStandDataDate {
     pub tree_stand_summary: Option<TreeStandSummary>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+impl TreeStandData {
+    /// Picks the snapshot that matches the selector, see `SnapshotSelector`
+    pub fn select(&self, selector: &SnapshotSelector) -> Option<&TreeStandDataDate> {
+        self.tree_stand_data_date
+            .iter()
+            .enumerate()
+            .filter(|(_, data_date)| selector.data_type.is_none_or(|data_type| data_date.tree_stand_data_date_type == data_type))
+            .filter(|(_, data_date)| match selector.as_of {
+                Some(as_of) => data_date.parsed_date().is_some_and(|date| date <= as_of),
+                None => true,
+            })
+            // Latest date wins, of snapshots with the same date the one that comes last in the file
+            .max_by_key(|(i, data_date)| (data_date.parsed_date(), *i))
+            .map(|(_, data_date)| data_date)
+    }
+}
+
+impl TreeStandDataDate {
+    pub fn parsed_date(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(self.date.trim(), "%Y-%m-%d").ok()
+    }
+}
+
+/// Selects one of the dated tree stand data snapshots of a stand.
+/// The default selects the latest snapshot of any type.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SnapshotSelector {
+    /// Only snapshots of this type, e.g. inventory data without predictions
+    pub data_type: Option<TreeStandDataType>,
+    /// Latest snapshot dated on or before this date
+    pub as_of: Option<NaiveDate>,
+}
+
+impl SnapshotSelector {
+    pub fn latest() -> Self {
+        SnapshotSelector::default()
+    }
+
+    pub fn of_type(data_type: TreeStandDataType) -> Self {
+        SnapshotSelector {
+            data_type: Some(data_type),
+            as_of: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct DeadTreeStrata {
     
     #[serde(rename = "DeadTreeStratum")]
@@ -376,17 +539,19 @@ pub struct DeadTreeStrata {
 pub struct DeadTreeStratum {
     #[serde(rename = "@id")]
     pub id: u32,
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     #[serde(rename = "DeadTreeType")]
-    pub dead_tree_type: u8,
+    pub dead_tree_type: DeadTreeType,
     #[serde(rename = "TreeSpecies")]
-    pub tree_species: u8,
+    pub tree_species: TreeSpecies,
     #[serde(rename = "Volume", default = "default_zero_f32")]
     pub volume: f32,
     #[serde(rename = "MeanDiameter", default = "default_zero_f32")]
     pub mean_diameter: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct TreeStrata {
     
     #[serde(rename = "TreeStratum")]
@@ -394,6 +559,16 @@ pub struct TreeStrata {
 }
 
 
+impl TreeStrata {
+    pub fn new(tree_stratum: Vec<TreeStratum>) -> Self {
+        TreeStrata { tree_stratum }
+    }
+
+    pub fn total_stem_count(&self) -> u32 {
+        self.tree_stratum.iter().map(|stratum| stratum.stem_count).sum()
+    }
+}
+
 fn default_zero_u32() -> u32 {
     0
 }
@@ -407,21 +582,23 @@ fn default_zero_f32() -> f32 {
 pub struct TreeStratum {
     #[serde(rename = "@id", default)]
     pub id: u32,
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     #[serde(rename = "StratumNumber")]
     pub stratum_number: u32,
     #[serde(rename = "TreeSpecies")]
-    pub tree_species: u8,
+    pub tree_species: TreeSpecies,
     #[serde(rename = "Storey")]
-    pub storey: u8,
+    pub storey: Storey,
     #[serde(rename = "Age")]
     pub age: u8,
     #[serde(rename = "StemCount", default = "default_zero_u32")]
     pub stem_count: u32,
     #[serde(rename = "MeanDiameter", default = "default_zero_f32")]
     pub mean_diameter: f32,
-    #[serde(rename = "MeanHeight")]
+    #[serde(rename = "MeanHeight", default = "default_zero_f32")]
     pub mean_height: f32,
-    #[serde(rename = "DataSource")]
+    #[serde(rename = "DataSource", default = "default_zero_u32")]
     pub data_source: u32,
     #[serde(rename = "BasalArea",  default = "default_zero_f32")]
     pub basal_area: f32,
@@ -437,10 +614,12 @@ pub struct TreeStratum {
     pub pulp_wood_volume: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Copy)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Copy)]
 pub struct TreeStandSummary {
     #[serde(rename = "@id")]
     pub id: u32,
+    #[serde(rename = "ChangeState", default)]
+    pub change_state: Option<ChangeState>,
     #[serde(rename = "PulpWoodVolume", default = "default_zero_f32")]
     pub pulp_wood_volume: f32,
     #[serde(rename = "SawLogVolume", default = "default_zero_f32")]
@@ -459,6 +638,123 @@ pub struct TreeStandSummary {
     pub volume: f32,
     #[serde(rename = "VolumeGrowth")]
     pub volume_growth: f32,
+    #[serde(rename = "Value", default)]
+    pub value: Option<f32>,
     #[serde(rename = "ValueGrowthPercent", default = "default_zero_f32")]
     pub value_growth_percent: f32,
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_get_stands_filtered() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let all_stands = property.get_stands();
+
+    // Filter by the real estate and parcel of the first stand
+    let first = &all_stands[0];
+    let filter = StandFilter { real_estate_id: first.real_estate_id, parcel_number: first.parcel_number };
+    let stands = property.get_stands_filtered(&filter);
+    let expected = all_stands.iter()
+        .filter(|stand| stand.real_estate_id == first.real_estate_id && stand.parcel_number == first.parcel_number)
+        .count();
+
+    assert!(!stands.is_empty() && stands.len() < all_stands.len());
+    assert_eq!(stands.len(), expected);
+    assert!(stands.iter().all(|stand| stand.real_estate_id == first.real_estate_id && stand.parcel_number == first.parcel_number));
+
+    let filter = StandFilter { real_estate_id: Some(1), parcel_number: None };
+    assert!(property.get_stands_filtered(&filter).is_empty());
+    assert!(property.get_bounding_box(&filter).is_none());
+
+    // Stands of every real estate are returned, each with its own real estate and parcel
+    let property = ForestPropertyData::from_xml_file("fixtures/two_real_estates.xml");
+    let stands = property.get_stands();
+    let ids: Vec<(&str, Option<u32>, Option<i64>)> = stands.iter()
+        .map(|stand| (stand.id.as_str(), stand.real_estate_id, stand.parcel_number))
+        .collect();
+    assert_eq!(ids, vec![("2553941", Some(526637), Some(0)), ("2553942", Some(526638), Some(3))]);
+
+    let filter = StandFilter { real_estate_id: Some(526638), parcel_number: None };
+    let stands = property.get_stands_filtered(&filter);
+    assert_eq!(stands.len(), 1);
+    assert_eq!(stands[0].id, "2553942");
+    assert!(property.get_bounding_box(&filter).is_some());
+}
+
+#[test]
+fn test_select_tree_stand_data() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stands = property.get_stands();
+    let has_type = |stand: &Stand, data_type: TreeStandDataType| {
+        stand.tree_stand_data_dates().iter().any(|date| date.tree_stand_data_date_type == data_type)
+    };
+    let stand = stands.iter()
+        .find(|stand| has_type(stand, TreeStandDataType::Inventory) && has_type(stand, TreeStandDataType::Updated))
+        .unwrap();
+
+    // Latest date wins, of snapshots with the same date the later entry
+    let latest_of = |data_type: Option<TreeStandDataType>| {
+        stand.tree_stand_data_dates().iter()
+            .enumerate()
+            .filter(|(_, date)| data_type.is_none_or(|data_type| date.tree_stand_data_date_type == data_type))
+            .max_by_key(|(i, date)| (date.parsed_date(), *i))
+            .map(|(_, date)| date)
+    };
+    let stem_count = |date: &TreeStandDataDate| date.tree_strata.tree_stratum.iter().map(|stratum| stratum.stem_count).sum::<u32>();
+
+    let latest = stand.select_tree_stand_data_date(&SnapshotSelector::latest()).unwrap();
+    assert!(std::ptr::eq(latest, latest_of(None).unwrap()));
+
+    for data_type in [TreeStandDataType::Inventory, TreeStandDataType::Updated] {
+        let selected = stand.select_tree_stand_data_date(&SnapshotSelector::of_type(data_type)).unwrap();
+        let expected = latest_of(Some(data_type)).unwrap();
+        assert_eq!(selected.tree_stand_data_date_type, data_type);
+        assert_eq!(selected.tree_strata.total_stem_count(), stem_count(expected));
+        assert!(std::ptr::eq(selected, expected));
+    }
+
+    let earliest = stand.tree_stand_data_dates().iter().filter_map(|date| date.parsed_date()).min().unwrap();
+    let before = SnapshotSelector { as_of: earliest.pred_opt(), ..Default::default() };
+    assert!(stand.select_tree_stand_data_date(&before).is_none());
+    assert_eq!(
+        stand.select_tree_stand_data_date(&SnapshotSelector::of_type(TreeStandDataType::Forecast)).is_some(),
+        has_type(stand, TreeStandDataType::Forecast)
+    );
+}
+
+#[test]
+fn test_typed_operations() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stands = property.get_stands();
+    let assortments: Vec<&Assortment> = stands.iter()
+        .flat_map(|stand| stand.operations())
+        .flat_map(|operation| operation.assortments())
+        .collect();
+
+    // An empty <op:TreeSpecies /> has no species, unlisted codes are kept
+    assert!(assortments.iter().any(|assortment| assortment.tree_species.is_none()));
+    assert!(assortments.iter().any(|assortment| assortment.tree_species == Some(TreeSpecies::ScotsPine)));
+    assert!(assortments.iter().any(|assortment| assortment.tree_species == Some(TreeSpecies::Unknown(103))));
+    assert!(stands.iter().flat_map(|stand| stand.operations()).any(|operation| operation.is_cutting()));
+
+    // Change states and summary values are kept in the typed model
+    assert!(assortments.iter().all(|assortment| assortment.change_state == Some(ChangeState::Unchanged)));
+    assert!(stands.iter().all(|stand| stand.stand_basic_data.change_state == Some(ChangeState::Unchanged)));
+    let data_dates: Vec<&TreeStandDataDate> = stands.iter().flat_map(|stand| stand.tree_stand_data_dates()).collect();
+    assert!(data_dates.iter().flat_map(|date| &date.tree_strata.tree_stratum).all(|stratum| stratum.change_state.is_some()));
+    let summaries: Vec<&TreeStandSummary> = data_dates.iter().filter_map(|date| date.tree_stand_summary.as_ref()).collect();
+
+    // Every summary has a value exactly where the source has <tss:Value>
+    let source = String::from_utf8_lossy(&std::fs::read("forestpropertydata.xml").unwrap()).into_owned();
+    let source_values: Vec<(u32, Option<f32>)> = source.split("<tss:TreeStandSummary id=\"").skip(1)
+        .map(|chunk| {
+            let summary = chunk.split("</tss:TreeStandSummary>").next().unwrap();
+            let id = summary.split('"').next().unwrap().parse().unwrap();
+            let value = summary.split("<tss:Value>").nth(1)
+                .map(|rest| rest.split('<').next().unwrap().trim().parse().unwrap());
+            (id, value)
+        })
+        .collect();
+    let values: Vec<(u32, Option<f32>)> = summaries.iter().map(|summary| (summary.id, summary.value)).collect();
+    assert!(source_values.iter().any(|(_, value)| value.is_some()));
+    assert_eq!(values, source_values);
+}
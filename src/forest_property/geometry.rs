@@ -1,3 +1,4 @@
+use geo::{Coord, LineString};
 use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Debug,Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -6,8 +7,34 @@ pub struct PolygonGeometry {
     pub text: Option<String>,
     #[serde(rename = "pointProperty")]
     pub point_property: PointProperty,
-    #[serde(rename = "polygonProperty")]
+    #[serde(rename = "polygonProperty", default)]
     pub polygon_property: PolygonProperty,
+    #[serde(rename = "multiPolygonProperty", alias = "multiSurfaceProperty", skip_serializing_if = "Option::is_none")]
+    pub multi_polygon_property: Option<PolygonProperty>,
+}
+
+impl PolygonGeometry {
+    // All polygons of the geometry, whether given as a single polygon or as members of a multi polygon
+    pub fn polygons(&self) -> Vec<&Polygon> {
+        let mut polygons: Vec<&Polygon> = self.polygon_property.polygons();
+        if let Some(property) = &self.multi_polygon_property {
+            polygons.extend(property.polygons());
+        }
+        polygons
+    }
+
+    // Spatial reference system of the polygons. Falls back to the point if the polygons don't declare one.
+    pub fn srs_name(&self) -> &str {
+        let mut srs_names: Vec<&str> = self.polygons().iter().map(|polygon| polygon.srs_name.as_str()).collect();
+        for property in [Some(&self.polygon_property), self.multi_polygon_property.as_ref()].into_iter().flatten() {
+            if let Some(multi_polygon) = &property.multi_polygon {
+                srs_names.push(&multi_polygon.srs_name);
+            }
+        }
+        srs_names.push(&self.point_property.point.srs_name);
+
+        srs_names.into_iter().find(|srs_name| !srs_name.is_empty()).unwrap_or("")
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug,Default, Clone, PartialEq)]
@@ -24,15 +51,63 @@ pub struct PointProperty {
 pub struct Point {
     #[serde(rename = "@srsName", default)]
     pub srs_name: String,
+    #[serde(rename = "@srsDimension", skip_serializing_if = "Option::is_none")]
+    pub srs_dimension: Option<usize>,
     #[serde(rename = "$text")]
     pub text: Option<String>,
-    #[serde(rename = "coordinates")]
-    pub coordinates: String,
+    #[serde(rename = "coordinates", skip_serializing_if = "Option::is_none")]
+    pub coordinates: Option<Coordinates>,
+    #[serde(rename = "pos", skip_serializing_if = "Option::is_none")]
+    pub pos: Option<DirectPositions>,
+}
+
+impl Point {
+    pub fn position(&self) -> Option<Coord<f64>> {
+        match (&self.coordinates, &self.pos) {
+            (Some(coordinates), _) => coordinates.positions().first().copied(),
+            (None, Some(pos)) => pos.positions(pos.srs_dimension.or(self.srs_dimension).unwrap_or(2)).first().copied(),
+            (None, None) => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug,Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PolygonProperty {
+    #[serde(rename = "$text")]
+    pub text: Option<String>,
+    #[serde(rename = "Polygon", skip_serializing_if = "Option::is_none")]
+    pub polygon: Option<Polygon>,
+    #[serde(rename = "MultiPolygon", alias = "MultiSurface", skip_serializing_if = "Option::is_none")]
+    pub multi_polygon: Option<MultiPolygon>,
+}
+
+impl PolygonProperty {
+    pub fn polygons(&self) -> Vec<&Polygon> {
+        let mut polygons: Vec<&Polygon> = self.polygon.iter().collect();
+        if let Some(multi_polygon) = &self.multi_polygon {
+            polygons.extend(multi_polygon.polygon_member.iter().map(|member| &member.polygon));
+        }
+        polygons
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug,Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiPolygon {
+    #[serde(rename = "@srsName", default)]
+    pub srs_name: String,
+    #[serde(rename = "@srsDimension", skip_serializing_if = "Option::is_none")]
+    pub srs_dimension: Option<usize>,
+    #[serde(rename = "$text")]
+    pub text: Option<String>,
+    #[serde(rename = "polygonMember", alias = "surfaceMember", default)]
+    pub polygon_member: Vec<PolygonMember>,
+}
+
+#[derive(Serialize, Deserialize, Debug,Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PolygonMember {
     #[serde(rename = "$text")]
     pub text: Option<String>,
     #[serde(rename = "Polygon")]
@@ -44,14 +119,38 @@ pub struct PolygonProperty {
 pub struct Polygon {
     #[serde(rename = "@srsName", default)]
     pub srs_name: String,
+    #[serde(rename = "@srsDimension", skip_serializing_if = "Option::is_none")]
+    pub srs_dimension: Option<usize>,
     #[serde(rename = "$text", default)]
     pub text: Option<String>,
-    #[serde(rename = "interior", default)]
+    // GML 2 uses innerBoundaryIs and outerBoundaryIs
+    #[serde(rename = "interior", alias = "innerBoundaryIs", default)]
     pub interior: Vec<Interior>,
-    #[serde(rename = "exterior")]
+    #[serde(rename = "exterior", alias = "outerBoundaryIs")]
     pub exterior: Exterior,
 }
 
+impl Polygon {
+    // Exterior and interior rings in the coordinates of the polygon's reference system
+    pub fn rings(&self) -> (LineString, Vec<LineString>) {
+        let exterior = LineString::new(self.exterior.linear_ring.positions(self.srs_dimension));
+        let interiors = self.interior
+            .iter()
+            .map(|interior| LineString::new(interior.linear_ring.positions(self.srs_dimension)))
+            .collect();
+
+        (exterior, interiors)
+    }
+
+    pub fn invalid_coordinates(&self) -> Vec<&str> {
+        let mut invalid = self.exterior.linear_ring.invalid_coordinates();
+        for interior in &self.interior {
+            invalid.extend(interior.linear_ring.invalid_coordinates());
+        }
+        invalid
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug,Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Interior {
@@ -76,6 +175,144 @@ pub struct Exterior {
 pub struct LinearRing {
     #[serde(rename = "$text")]
     pub text: Option<String>,
-    #[serde(rename = "coordinates")]
-    pub coordinates: String,
-}
\ No newline at end of file
+    #[serde(rename = "coordinates", skip_serializing_if = "Option::is_none")]
+    pub coordinates: Option<Coordinates>,
+    #[serde(rename = "posList", skip_serializing_if = "Option::is_none")]
+    pub pos_list: Option<DirectPositions>,
+    #[serde(rename = "pos", default, skip_serializing_if = "Vec::is_empty")]
+    pub pos: Vec<DirectPositions>,
+}
+
+impl LinearRing {
+    // Positions of the ring from whichever of coordinates, posList or pos elements it has.
+    // `srs_dimension` is the dimension inherited from the polygon.
+    pub fn positions(&self, srs_dimension: Option<usize>) -> Vec<Coord<f64>> {
+        if let Some(coordinates) = &self.coordinates {
+            return coordinates.positions();
+        }
+        if let Some(pos_list) = &self.pos_list {
+            return pos_list.positions(pos_list.srs_dimension.or(srs_dimension).unwrap_or(2));
+        }
+
+        self.pos.iter()
+            .flat_map(|pos| pos.positions(pos.srs_dimension.or(srs_dimension).unwrap_or(2)))
+            .collect()
+    }
+
+    // Coordinates of the ring that can't be parsed
+    pub fn invalid_coordinates(&self) -> Vec<&str> {
+        if let Some(coordinates) = &self.coordinates {
+            return coordinates.invalid_tuples();
+        }
+        if let Some(pos_list) = &self.pos_list {
+            return pos_list.invalid_numbers();
+        }
+
+        self.pos.iter().flat_map(|pos| pos.invalid_numbers()).collect()
+    }
+}
+
+/// `gml:coordinates` tuples. The decimal, coordinate and tuple separators default to `.`, `,` and whitespace.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Coordinates {
+    #[serde(rename = "@decimal", skip_serializing_if = "Option::is_none")]
+    pub decimal: Option<String>,
+    #[serde(rename = "@cs", skip_serializing_if = "Option::is_none")]
+    pub cs: Option<String>,
+    #[serde(rename = "@ts", skip_serializing_if = "Option::is_none")]
+    pub ts: Option<String>,
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+impl Coordinates {
+    pub fn new(value: &str) -> Self {
+        Coordinates {
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Only x and y are used from 3D tuples. Tuples that can't be parsed are skipped, see `invalid_tuples`.
+    pub fn positions(&self) -> Vec<Coord<f64>> {
+        self.tuples().filter_map(|tuple| self.parse_tuple(tuple)).collect()
+    }
+
+    pub fn invalid_tuples(&self) -> Vec<&str> {
+        self.tuples().filter(|tuple| self.parse_tuple(tuple).is_none()).collect()
+    }
+
+    fn tuples(&self) -> impl Iterator<Item = &str> {
+        let tuples: Vec<&str> = match self.ts.as_deref() {
+            Some(ts) if !ts.trim().is_empty() => self.value.split(ts).map(str::trim).collect(),
+            _ => self.value.split_whitespace().collect(),
+        };
+        tuples.into_iter().filter(|tuple| !tuple.is_empty())
+    }
+
+    fn parse_tuple(&self, tuple: &str) -> Option<Coord<f64>> {
+        let cs = self.cs.as_deref().unwrap_or(",");
+        let decimal = self.decimal.as_deref().unwrap_or(".");
+
+        let parts: Vec<&str> = tuple.split(cs).map(str::trim).collect();
+        let (x, y) = match parts.as_slice() {
+            [x, y] | [x, y, _] => parse_number(x, decimal).zip(parse_number(y, decimal))?,
+            _ => return None,
+        };
+        Some(Coord { x, y })
+    }
+}
+
+/// `gml:pos` or `gml:posList`: whitespace separated numbers, `srsDimension` numbers per position.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct DirectPositions {
+    #[serde(rename = "@srsDimension", skip_serializing_if = "Option::is_none")]
+    pub srs_dimension: Option<usize>,
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+impl DirectPositions {
+    pub fn positions(&self, srs_dimension: usize) -> Vec<Coord<f64>> {
+        // Numbers that can't be parsed are skipped, see `invalid_numbers`
+        let numbers: Vec<f64> = self.value
+            .split_whitespace()
+            .filter_map(|number| parse_number(number, "."))
+            .collect();
+
+        numbers.chunks_exact(srs_dimension.max(2))
+            .map(|position| Coord { x: position[0], y: position[1] })
+            .collect()
+    }
+
+    pub fn invalid_numbers(&self) -> Vec<&str> {
+        self.value.split_whitespace().filter(|number| parse_number(number, ".").is_none()).collect()
+    }
+}
+
+fn parse_number(number: &str, decimal: &str) -> Option<f64> {
+    if decimal == "." {
+        number.parse().ok()
+    } else {
+        number.replace(decimal, ".").parse().ok()
+    }
+}
+
+#[test]
+fn test_gml_positions() {
+    let coordinates = Coordinates { decimal: Some(",".to_string()), cs: Some(";".to_string()), ts: Some("|".to_string()), value: "1,5;2|3;4,5".to_string() };
+    assert_eq!(coordinates.positions(), vec![Coord { x: 1.5, y: 2.0 }, Coord { x: 3.0, y: 4.5 }]);
+    assert_eq!(Coordinates::new("1,2,10 3,4,20").positions(), vec![Coord { x: 1.0, y: 2.0 }, Coord { x: 3.0, y: 4.0 }]);
+    assert_eq!(Coordinates::new("1,2 3;4 5,y").invalid_tuples(), vec!["3;4", "5,y"]);
+
+    let xml = r#"<Polygon srsName="EPSG:3067" srsDimension="3">
+        <exterior><LinearRing><posList>0 0 1 10 0 1 10 10 1 0 0 1</posList></LinearRing></exterior>
+        <interior><LinearRing><pos>1 1 0</pos><pos>2 1 0</pos><pos>2 2 0</pos><pos>1 1 0</pos></LinearRing></interior>
+    </Polygon>"#;
+    let polygon: Polygon = quick_xml::de::from_str(xml).unwrap();
+    let (exterior, interiors) = polygon.rings();
+
+    assert_eq!(exterior.0.len(), 4);
+    assert_eq!(exterior.0[2], Coord { x: 10.0, y: 10.0 });
+    assert_eq!(interiors[0].0[1], Coord { x: 2.0, y: 1.0 });
+}
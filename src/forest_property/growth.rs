@@ -0,0 +1,147 @@
+use chrono::NaiveDate;
+use super::codes::{ChangeState, TreeStandDataType};
+use super::stand::Stand;
+use super::tree_stand_data::{SnapshotSelector, TreeStandDataDate, TreeStandSummary, TreeStrata, TreeStratum};
+
+const DAYS_PER_YEAR: f64 = 365.25;
+
+/// Grows tree strata forward in time. Implement this to plug in a different growth model.
+pub trait GrowthModel {
+    /// Returns the stratum as it is expected to be after `years` years
+    fn grow_stratum(&self, stratum: &TreeStratum, years: f64) -> TreeStratum;
+}
+
+/// Grows the volume of each stratum by its own `VolumeGrowth`, m³/ha per year.
+/// Stem count stays the same, so mean height and mean diameter grow by the cube root
+/// of the relative volume change and basal area by the square of that.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct VolumeGrowthModel;
+
+impl GrowthModel for VolumeGrowthModel {
+    fn grow_stratum(&self, stratum: &TreeStratum, years: f64) -> TreeStratum {
+        let mut grown = stratum.to_owned();
+        grown.age = (stratum.age as f64 + years).round().min(u8::MAX as f64) as u8;
+
+        let growth = stratum.volume_growth * years as f32;
+        if growth <= 0.0 {
+            return grown;
+        }
+        grown.volume = stratum.volume + growth;
+
+        // Without a volume to scale there is nothing to derive the dimensions from
+        if stratum.volume > 0.0 {
+            let ratio = grown.volume / stratum.volume;
+            let scale = ratio.cbrt();
+            grown.mean_height = stratum.mean_height * scale;
+            grown.mean_diameter = stratum.mean_diameter * scale;
+            grown.basal_area = stratum.basal_area * scale * scale;
+            grown.saw_log_volume = stratum.saw_log_volume * ratio;
+            grown.pulp_wood_volume = stratum.pulp_wood_volume * ratio;
+        }
+
+        grown
+    }
+}
+
+/// Projects a snapshot to `date` and returns it as a new forecast snapshot.
+/// Returns None if the snapshot has no valid date or `date` is before it.
+pub fn project_tree_stand_data(
+    data_date: &TreeStandDataDate,
+    model: &dyn GrowthModel,
+    date: NaiveDate
+) -> Option<TreeStandDataDate> {
+    let days = (date - data_date.parsed_date()?).num_days();
+    if days < 0 {
+        return None;
+    }
+    let years = days as f64 / DAYS_PER_YEAR;
+
+    let strata: Vec<TreeStratum> = data_date.tree_strata.tree_stratum
+        .iter()
+        .map(|stratum| model.grow_stratum(stratum, years))
+        .collect();
+    let summary = data_date.tree_stand_summary.map(|summary| summarize_strata(&strata, &summary, years));
+
+    Some(TreeStandDataDate {
+        date: date.format("%Y-%m-%d").to_string(),
+        tree_stand_data_date_type: TreeStandDataType::Forecast,
+        dead_tree_strata: data_date.dead_tree_strata.to_owned(),
+        tree_strata: TreeStrata::new(strata),
+        tree_stand_summary: summary,
+    })
+}
+
+// Summary of grown strata. Means are weighted by basal area, and the value grows by
+// the value growth percent of the previous summary.
+fn summarize_strata(strata: &[TreeStratum], previous: &TreeStandSummary, years: f64) -> TreeStandSummary {
+    let basal_area: f32 = strata.iter().map(|stratum| stratum.basal_area).sum();
+    let weighted_mean = |value: fn(&TreeStratum) -> f32| -> f32 {
+        if basal_area > 0.0 {
+            strata.iter().map(|stratum| value(stratum) * stratum.basal_area).sum::<f32>() / basal_area
+        } else {
+            0.0
+        }
+    };
+
+    TreeStandSummary {
+        id: previous.id,
+        // The projected summary is new data
+        change_state: previous.change_state.map(|_| ChangeState::Added),
+        pulp_wood_volume: strata.iter().map(|stratum| stratum.pulp_wood_volume).sum(),
+        saw_log_volume: strata.iter().map(|stratum| stratum.saw_log_volume).sum(),
+        mean_age: weighted_mean(|stratum| stratum.age as f32),
+        basal_area,
+        stem_count: strata.iter().map(|stratum| stratum.stem_count).sum(),
+        mean_diameter: weighted_mean(|stratum| stratum.mean_diameter),
+        mean_height: weighted_mean(|stratum| stratum.mean_height),
+        volume: strata.iter().map(|stratum| stratum.volume).sum(),
+        volume_growth: strata.iter().map(|stratum| stratum.volume_growth).sum(),
+        value: previous.value.map(|value| value * (1.0 + previous.value_growth_percent / 100.0).powf(years as f32)),
+        value_growth_percent: previous.value_growth_percent,
+    }
+}
+
+impl Stand {
+    /// Projects the latest snapshot of the stand to `date` and adds the projection
+    /// as a forecast snapshot, so that it is selected as the latest data.
+    pub fn project_growth(&mut self, model: &dyn GrowthModel, date: NaiveDate) -> Option<&TreeStandDataDate> {
+        self.project_growth_from(&SnapshotSelector::default(), model, date)
+    }
+
+    pub fn project_growth_from(
+        &mut self,
+        selector: &SnapshotSelector,
+        model: &dyn GrowthModel,
+        date: NaiveDate
+    ) -> Option<&TreeStandDataDate> {
+        let projected = project_tree_stand_data(self.select_tree_stand_data_date(selector)?, model, date)?;
+        let data = self.tree_stand_data.as_mut()?;
+        data.tree_stand_data_date.push(projected);
+        data.tree_stand_data_date.last()
+    }
+}
+
+#[test]
+fn test_project_growth() {
+    use super::forest_property_data::ForestPropertyData;
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let mut stand = property.get_stands().into_iter().find(|stand| stand.id == "2554724").unwrap();
+    let base = stand.get_last_tree_stand_data_date().unwrap();
+
+    let date = NaiveDate::from_ymd_opt(2030, 10, 29).unwrap();
+    let projected = stand.project_growth(&VolumeGrowthModel, date).unwrap().to_owned();
+    assert_eq!(projected.tree_stand_data_date_type, TreeStandDataType::Forecast);
+    assert_eq!(stand.get_last_tree_stand_data_date().unwrap(), projected);
+
+    for (before, after) in base.tree_strata.tree_stratum.iter().zip(&projected.tree_strata.tree_stratum) {
+        assert_eq!(after.age, before.age + 11);
+        assert!((after.volume - (before.volume + before.volume_growth * 11.0)).abs() < 0.1);
+        assert!(after.mean_height >= before.mean_height);
+    }
+
+    // The original snapshot can still be selected
+    let as_of = SnapshotSelector { as_of: NaiveDate::from_ymd_opt(2020, 1, 1), ..Default::default() };
+    assert_eq!(stand.select_tree_stand_data_date(&as_of), Some(&base));
+    assert!(project_tree_stand_data(&base, &VolumeGrowthModel, NaiveDate::from_ymd_opt(2000, 1, 1).unwrap()).is_none());
+}
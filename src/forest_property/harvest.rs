@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use chrono::Datelike;
+use geo::Polygon;
+use geojson::{FeatureCollection, GeoJson};
+use serde::Serialize;
+use crate::geojson_utils::{compartment_properties, convert_polygon_to_feature};
+use super::codes::{OperationType, TreeSpecies};
+use super::csv_utils::csv_field;
+use super::forest_property_data::{ForestPropertyData, StandFilter};
+
+/// A proposed operation that has not been completed
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduledOperation {
+    pub stand_id: String,
+    pub stand_number: String,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+    /// Hectares
+    pub stand_area: f64,
+    pub operation_id: u32,
+    pub operation_type: OperationType,
+    pub proposal_type: u32,
+    pub proposal_year: u32,
+    /// Cubic metres, zero for operations that are not cuttings
+    pub cutting_volume: f64,
+    /// The proposal year has passed
+    pub overdue: bool,
+    /// Every part of the stand
+    #[serde(skip)]
+    pub polygons: Vec<Polygon>,
+}
+
+/// Proposed cutting volume of one assortment (stem type) and species in one year
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AssortmentTotal {
+    pub proposal_year: u32,
+    pub tree_species: Option<TreeSpecies>,
+    pub stem_type: u32,
+    pub volume: f64,
+}
+
+/// Options of `ForestPropertyData::harvest_schedule_with_options`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HarvestOptions {
+    pub filter: StandFilter,
+    /// Proposals before this year are overdue. Defaults to the current year.
+    pub reference_year: Option<u32>,
+}
+
+/// Proposed operations of a property ordered by year, with assortment totals of the cuttings.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct HarvestSchedule {
+    pub reference_year: u32,
+    pub operations: Vec<ScheduledOperation>,
+    pub assortments: Vec<AssortmentTotal>,
+}
+
+impl HarvestSchedule {
+    pub fn years(&self) -> Vec<u32> {
+        let mut years: Vec<u32> = self.operations.iter().map(|operation| operation.proposal_year).collect();
+        years.dedup();
+        years
+    }
+
+    pub fn operations_in(&self, year: u32) -> impl Iterator<Item = &ScheduledOperation> {
+        self.operations.iter().filter(move |operation| operation.proposal_year == year)
+    }
+
+    pub fn overdue(&self) -> impl Iterator<Item = &ScheduledOperation> {
+        self.operations.iter().filter(|operation| operation.overdue)
+    }
+
+    pub fn cutting_volume_in(&self, year: u32) -> f64 {
+        self.operations_in(year).map(|operation| operation.cutting_volume).sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    // Table of the operations, one line per operation
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "proposal_year,real_estate_id,parcel_number,stand_id,stand_number,stand_area,operation_id,operation_type,proposal_type,cutting_volume,overdue\n"
+        );
+        for operation in &self.operations {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{:.4},{},{},{},{:.2},{}",
+                operation.proposal_year,
+                operation.real_estate_id.map(|id| id.to_string()).unwrap_or_default(),
+                operation.parcel_number.map(|number| number.to_string()).unwrap_or_default(),
+                csv_field(&operation.stand_id),
+                csv_field(&operation.stand_number),
+                operation.stand_area,
+                operation.operation_id,
+                operation.operation_type.code(),
+                operation.proposal_type,
+                operation.cutting_volume,
+                operation.overdue
+            );
+        }
+        csv
+    }
+
+    // Table of the assortment totals. Tree species is empty if the assortment has none.
+    pub fn assortments_to_csv(&self) -> String {
+        let mut csv = String::from("proposal_year,tree_species,stem_type,volume\n");
+        for total in &self.assortments {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{:.2}",
+                total.proposal_year,
+                total.tree_species.map(|species| species.code().to_string()).unwrap_or_default(),
+                total.stem_type,
+                total.volume
+            );
+        }
+        csv
+    }
+
+    /// One polygon feature per part of an affected stand, with its first proposal year,
+    /// total cutting volume, operation types and whether any proposal is overdue.
+    pub fn to_geojson(&self) -> GeoJson {
+        let mut stands: BTreeMap<&str, Vec<&ScheduledOperation>> = BTreeMap::new();
+        for operation in &self.operations {
+            stands.entry(&operation.stand_id).or_default().push(operation);
+        }
+
+        let features = stands
+            .values()
+            .flat_map(|operations| {
+                let first = operations[0];
+
+                let mut properties = compartment_properties(&first.stand_number, first.real_estate_id, first.parcel_number);
+                properties.insert("stand_id".to_string(), serde_json::json!(first.stand_id));
+                properties.insert(
+                    "first_proposal_year".to_string(),
+                    serde_json::json!(operations.iter().map(|operation| operation.proposal_year).min())
+                );
+                properties.insert(
+                    "cutting_volume".to_string(),
+                    serde_json::json!(operations.iter().map(|operation| operation.cutting_volume).sum::<f64>())
+                );
+                properties.insert(
+                    "operation_types".to_string(),
+                    serde_json::json!(operations.iter().map(|operation| operation.operation_type.code()).collect::<Vec<u16>>())
+                );
+                properties.insert(
+                    "overdue".to_string(),
+                    serde_json::json!(operations.iter().any(|operation| operation.overdue))
+                );
+
+                first.polygons
+                    .iter()
+                    .map(move |polygon| convert_polygon_to_feature(polygon, Some(properties.clone())))
+            })
+            .collect();
+
+        GeoJson::FeatureCollection(FeatureCollection {
+            features,
+            bbox: None,
+            foreign_members: None,
+        })
+    }
+}
+
+impl ForestPropertyData {
+    pub fn harvest_schedule(&self) -> HarvestSchedule {
+        self.harvest_schedule_with_options(&HarvestOptions::default())
+    }
+
+    pub fn harvest_schedule_with_options(&self, options: &HarvestOptions) -> HarvestSchedule {
+        let reference_year = options.reference_year.unwrap_or_else(|| chrono::Local::now().year() as u32);
+        let mut operations = Vec::new();
+        let mut assortments: BTreeMap<(u32, Option<u8>, u32), f64> = BTreeMap::new();
+
+        for stand in self.get_stands_filtered(&options.filter) {
+            let basic_data = &stand.stand_basic_data;
+            for operation in stand.operations().iter().filter(|operation| operation.completion_data.is_none()) {
+                let proposal_year = operation.proposal_data.proposal_year;
+                for assortment in operation.assortments() {
+                    let key = (proposal_year, assortment.tree_species.map(|species| species.code()), assortment.stem_type);
+                    *assortments.entry(key).or_default() += assortment.assortment_volume as f64;
+                }
+
+                operations.push(ScheduledOperation {
+                    stand_id: stand.id.to_owned(),
+                    stand_number: format!("{}{}", basic_data.stand_number, basic_data.stand_number_extension),
+                    real_estate_id: stand.real_estate_id,
+                    parcel_number: stand.parcel_number,
+                    stand_area: basic_data.area as f64,
+                    operation_id: operation.id,
+                    operation_type: operation.operation_type,
+                    proposal_type: operation.proposal_data.proposal_type,
+                    proposal_year,
+                    cutting_volume: operation.cutting.as_ref().map_or(0.0, |cutting| cutting.cutting_volume as f64),
+                    overdue: proposal_year < reference_year,
+                    polygons: stand.polygons().to_vec(),
+                });
+            }
+        }
+        // Stable sort keeps the file order within a year
+        operations.sort_by_key(|operation| operation.proposal_year);
+
+        let assortments = assortments
+            .into_iter()
+            .map(|((proposal_year, species, stem_type), volume)| AssortmentTotal {
+                proposal_year,
+                tree_species: species.map(TreeSpecies::from_code),
+                stem_type,
+                volume,
+            })
+            .collect();
+
+        HarvestSchedule {
+            reference_year,
+            operations,
+            assortments,
+        }
+    }
+}
+
+#[test]
+fn test_harvest_schedule() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let options = HarvestOptions { reference_year: Some(2020), ..Default::default() };
+    let schedule = property.harvest_schedule_with_options(&options);
+
+    assert!(!schedule.operations.is_empty());
+    assert!(schedule.operations.windows(2).all(|pair| pair[0].proposal_year <= pair[1].proposal_year));
+    assert!(schedule.overdue().all(|operation| operation.proposal_year < 2020));
+
+    // Assortment totals of a year add up to the assortment volumes of its cuttings
+    let year = schedule.years()[0];
+    let assortment_volume: f64 = schedule.assortments.iter()
+        .filter(|total| total.proposal_year == year)
+        .map(|total| total.volume)
+        .sum();
+    let stands = property.get_stands();
+    let expected: f64 = stands.iter()
+        .flat_map(|stand| stand.operations())
+        .filter(|operation| operation.completion_data.is_none() && operation.proposal_data.proposal_year == year)
+        .flat_map(|operation| operation.assortments())
+        .map(|assortment| assortment.assortment_volume as f64)
+        .sum();
+    assert!((assortment_volume - expected).abs() < 1e-6);
+
+    let mut quoted = schedule.clone();
+    quoted.operations[0].stand_number = "12,\"a\"".to_string();
+    assert!(quoted.to_csv().lines().nth(1).unwrap().contains(",\"12,\"\"a\"\"\","));
+
+    let GeoJson::FeatureCollection(collection) = schedule.to_geojson() else { panic!("Expected a feature collection") };
+    let stand_parts: std::collections::HashMap<&str, usize> = schedule.operations.iter()
+        .map(|operation| (operation.stand_id.as_str(), operation.polygons.len()))
+        .collect();
+    assert!(stand_parts.values().all(|&parts| parts > 0));
+    assert_eq!(collection.features.len(), stand_parts.values().sum::<usize>());
+}
@@ -1,5 +1,7 @@
+use crate::forest_property::dead_wood::{DeadTree, DeadWoodGeometry};
 use crate::geometry_utils::get_min_max_coordinates;
-use geo_types::{Coord, Polygon};
+use geo::Contains;
+use geo_types::{Coord, LineString, Polygon};
 use image::{Rgb, RgbImage};
 
 pub struct ImageProcessor {
@@ -101,6 +103,48 @@ impl ImageProcessor {
         }
     }
 
+    // Draws the outline of a polygon and hatches its inside with diagonal lines, e.g. for special feature zones
+    pub fn draw_hatched_polygon(&mut self, coords: &[(u32, u32)], color: Rgb<u8>, spacing: u32) {
+        if coords.len() < 3 {
+            return;
+        }
+        self.draw_polygon_image(&coords.to_vec(), color);
+
+        let ring: Vec<(f64, f64)> = coords.iter().map(|&(x, y)| (x as f64, y as f64)).collect();
+        let polygon = Polygon::new(LineString::from(ring), vec![]);
+        let max_x = coords.iter().map(|coord| coord.0).max().unwrap_or(0).min(self.width - 1);
+        let max_y = coords.iter().map(|coord| coord.1).max().unwrap_or(0).min(self.height - 1);
+        let min_x = coords.iter().map(|coord| coord.0).min().unwrap_or(0);
+        let min_y = coords.iter().map(|coord| coord.1).min().unwrap_or(0);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if (x + y) % spacing.max(1) == 0 && polygon.contains(&Coord { x: x as f64, y: y as f64 }) {
+                    self.img.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    // Draws a snag as a point and a fallen log as a line
+    pub fn draw_dead_tree(&mut self, scale: &Scale, dead_tree: &DeadTree, color: Rgb<u8>) {
+        let (img_width, img_height) = (self.width, self.height);
+        match dead_tree.geometry {
+            DeadWoodGeometry::Standing(point) => self.draw_random_point(scale, img_width, img_height, point.0, color),
+            DeadWoodGeometry::Fallen(line) => {
+                let start = self.map_coordinate_to_image(line.start, scale);
+                let end = self.map_coordinate_to_image(line.end, scale);
+                self.draw_line_segment(start, end, color);
+            }
+        }
+    }
+
+    fn map_coordinate_to_image(&self, coord: Coord, scale: &Scale) -> (u32, u32) {
+        let x = ((coord.x - scale.min_x) * scale.scale_x).round().clamp(0.0, (self.width - 1) as f64) as u32;
+        let y = (self.height as f64 - (coord.y - scale.min_y) * scale.scale_y).round().clamp(0.0, (self.height - 1) as f64) as u32;
+        (x, y)
+    }
+
     // Draws a random point
     pub fn draw_random_point(&mut self, scale: &Scale, img_width: u32, img_height: u32, point: Coord, color: Rgb<u8>) {
         let Scale{scale_x, scale_y, min_x, min_y} = scale;
@@ -114,4 +158,19 @@ impl ImageProcessor {
 
         self.img.put_pixel(x, y, color);
     } 
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_draw_hatched_polygon() {
+    let color = Rgb([192, 57, 155]);
+    let mut image = ImageProcessor::new(60, 60);
+    image.draw_hatched_polygon(&[(10, 10), (50, 10), (50, 50), (10, 50), (10, 10)], color, 6);
+
+    // Every sixth diagonal inside the polygon is drawn, the outline too
+    assert_eq!(*image.img().get_pixel(30, 30), color);
+    assert_eq!(*image.img().get_pixel(31, 30), Rgb([0, 0, 0]));
+    assert_eq!(*image.img().get_pixel(10, 31), color);
+    assert_eq!(*image.img().get_pixel(5, 7), Rgb([0, 0, 0]));
+    let hatched = image.img().enumerate_pixels().filter(|(x, y, pixel)| (11..50).contains(x) && (11..50).contains(y) && **pixel == color).count();
+    assert!(hatched > 39 * 39 / 6 - 39 && hatched < 39 * 39 / 6 + 39);
+}
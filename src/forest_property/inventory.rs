@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use serde::Serialize;
+use super::codes::TreeSpecies;
+use super::csv_utils::csv_field;
+use super::forest_property_data::{ForestPropertyData, StandFilter};
+use super::stand::Stand;
+use super::tree_stand_data::{SnapshotSelector, TreeStratum};
+
+/// Volumes in m³, basal area in m², volume growth in m³ per year.
+/// Per hectare figures use the same fields.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct VolumeTotals {
+    pub volume: f64,
+    pub saw_log_volume: f64,
+    pub pulp_wood_volume: f64,
+    pub basal_area: f64,
+    pub stem_count: f64,
+    pub volume_growth: f64,
+}
+
+impl VolumeTotals {
+    // Stratum values are per hectare, `area` is the area of the stand in hectares
+    pub fn from_stratum(stratum: &TreeStratum, area: f64) -> Self {
+        VolumeTotals {
+            volume: stratum.volume as f64 * area,
+            saw_log_volume: stratum.saw_log_volume as f64 * area,
+            pulp_wood_volume: stratum.pulp_wood_volume as f64 * area,
+            basal_area: stratum.basal_area as f64 * area,
+            stem_count: stratum.stem_count as f64 * area,
+            volume_growth: stratum.volume_growth as f64 * area,
+        }
+    }
+
+    pub fn add(&mut self, other: &VolumeTotals) {
+        self.volume += other.volume;
+        self.saw_log_volume += other.saw_log_volume;
+        self.pulp_wood_volume += other.pulp_wood_volume;
+        self.basal_area += other.basal_area;
+        self.stem_count += other.stem_count;
+        self.volume_growth += other.volume_growth;
+    }
+
+    // Zero for an area of zero
+    pub fn per_hectare(&self, area: f64) -> Self {
+        if area <= 0.0 {
+            return VolumeTotals::default();
+        }
+
+        VolumeTotals {
+            volume: self.volume / area,
+            saw_log_volume: self.saw_log_volume / area,
+            pulp_wood_volume: self.pulp_wood_volume / area,
+            basal_area: self.basal_area / area,
+            stem_count: self.stem_count / area,
+            volume_growth: self.volume_growth / area,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InventoryLevel {
+    Property,
+    RealEstate,
+    Parcel,
+    Stand,
+}
+
+impl InventoryLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InventoryLevel::Property => "property",
+            InventoryLevel::RealEstate => "real_estate",
+            InventoryLevel::Parcel => "parcel",
+            InventoryLevel::Stand => "stand",
+        }
+    }
+}
+
+/// Totals of one stand, parcel, real estate or the whole property,
+/// either for all species (`tree_species` is None) or for one species.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InventoryRow {
+    pub level: InventoryLevel,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+    pub stand_id: Option<String>,
+    pub stand_number: Option<String>,
+    pub tree_species: Option<TreeSpecies>,
+    /// Hectares
+    pub area: f64,
+    pub totals: VolumeTotals,
+    pub per_hectare: VolumeTotals,
+}
+
+/// Options of `ForestPropertyData::inventory_summary_with_options`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InventoryOptions {
+    pub filter: StandFilter,
+    // Tree stand data snapshot the totals are computed from
+    pub snapshot: SnapshotSelector,
+}
+
+/// Volume and assortment totals of a property. Rows are ordered from the property
+/// down to the stands, each unit followed by its per species rows.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct InventorySummary {
+    pub rows: Vec<InventoryRow>,
+}
+
+impl InventorySummary {
+    pub fn rows_at(&self, level: InventoryLevel) -> impl Iterator<Item = &InventoryRow> {
+        self.rows.iter().filter(move |row| row.level == level)
+    }
+
+    // All species total of the whole property
+    pub fn property_total(&self) -> Option<&InventoryRow> {
+        self.rows_at(InventoryLevel::Property).find(|row| row.tree_species.is_none())
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    // One line per row. Tree species is written as its code, empty for all species.
+    pub fn to_csv(&self) -> String {
+        let fields = ["volume", "saw_log_volume", "pulp_wood_volume", "basal_area", "stem_count", "volume_growth"];
+        let mut csv = String::from("level,real_estate_id,parcel_number,stand_id,stand_number,tree_species,area");
+        for prefix in ["", "per_ha_"] {
+            for field in fields {
+                csv.push(',');
+                csv.push_str(prefix);
+                csv.push_str(field);
+            }
+        }
+        csv.push('\n');
+
+        for row in &self.rows {
+            let _ = write!(
+                csv,
+                "{},{},{},{},{},{},{:.4}",
+                row.level.as_str(),
+                optional(row.real_estate_id),
+                optional(row.parcel_number),
+                csv_field(row.stand_id.as_deref().unwrap_or("")),
+                csv_field(row.stand_number.as_deref().unwrap_or("")),
+                optional(row.tree_species.map(|species| species.code())),
+                row.area
+            );
+            for totals in [&row.totals, &row.per_hectare] {
+                let _ = write!(
+                    csv,
+                    ",{:.2},{:.2},{:.2},{:.2},{:.2},{:.2}",
+                    totals.volume,
+                    totals.saw_log_volume,
+                    totals.pulp_wood_volume,
+                    totals.basal_area,
+                    totals.stem_count,
+                    totals.volume_growth
+                );
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// Running totals of one unit, for all species and by species code
+#[derive(Default)]
+struct Accumulator {
+    area: f64,
+    totals: VolumeTotals,
+    species: BTreeMap<u8, VolumeTotals>,
+}
+
+impl Accumulator {
+    fn add(&mut self, other: &Accumulator) {
+        self.area += other.area;
+        self.totals.add(&other.totals);
+        for (code, totals) in &other.species {
+            self.species.entry(*code).or_default().add(totals);
+        }
+    }
+
+    fn push_rows(&self, row: InventoryRow, rows: &mut Vec<InventoryRow>) {
+        let species_rows: Vec<InventoryRow> = self.species
+            .iter()
+            .map(|(code, totals)| InventoryRow {
+                tree_species: Some(TreeSpecies::from_code(*code)),
+                area: self.area,
+                totals: *totals,
+                per_hectare: totals.per_hectare(self.area),
+                ..row.clone()
+            })
+            .collect();
+
+        rows.push(InventoryRow {
+            area: self.area,
+            totals: self.totals,
+            per_hectare: self.totals.per_hectare(self.area),
+            ..row
+        });
+        rows.extend(species_rows);
+    }
+}
+
+fn stand_accumulator(stand: &Stand, snapshot: &SnapshotSelector) -> Accumulator {
+    let area = stand.stand_basic_data.area as f64;
+    let mut accumulator = Accumulator { area, ..Default::default() };
+
+    if let Some(strata) = stand.get_strata_for(snapshot) {
+        for stratum in &strata.tree_stratum {
+            let totals = VolumeTotals::from_stratum(stratum, area);
+            accumulator.totals.add(&totals);
+            accumulator.species.entry(stratum.tree_species.code()).or_default().add(&totals);
+        }
+    }
+
+    accumulator
+}
+
+fn empty_row(level: InventoryLevel) -> InventoryRow {
+    InventoryRow {
+        level,
+        real_estate_id: None,
+        parcel_number: None,
+        stand_id: None,
+        stand_number: None,
+        tree_species: None,
+        area: 0.0,
+        totals: VolumeTotals::default(),
+        per_hectare: VolumeTotals::default(),
+    }
+}
+
+impl ForestPropertyData {
+    pub fn inventory_summary(&self) -> InventorySummary {
+        self.inventory_summary_with_options(&InventoryOptions::default())
+    }
+
+    pub fn inventory_summary_with_options(&self, options: &InventoryOptions) -> InventorySummary {
+        let stands = self.get_stands_filtered(&options.filter);
+
+        let mut property = Accumulator::default();
+        let mut real_estates: BTreeMap<Option<u32>, Accumulator> = BTreeMap::new();
+        let mut parcels: BTreeMap<(Option<u32>, Option<i64>), Accumulator> = BTreeMap::new();
+        let mut stand_rows = Vec::new();
+
+        for stand in &stands {
+            let accumulator = stand_accumulator(stand, &options.snapshot);
+            property.add(&accumulator);
+            real_estates.entry(stand.real_estate_id).or_default().add(&accumulator);
+            parcels.entry((stand.real_estate_id, stand.parcel_number)).or_default().add(&accumulator);
+
+            let basic_data = &stand.stand_basic_data;
+            let row = InventoryRow {
+                real_estate_id: stand.real_estate_id,
+                parcel_number: stand.parcel_number,
+                stand_id: Some(stand.id.to_owned()),
+                stand_number: Some(format!("{}{}", basic_data.stand_number, basic_data.stand_number_extension)),
+                ..empty_row(InventoryLevel::Stand)
+            };
+            accumulator.push_rows(row, &mut stand_rows);
+        }
+
+        let mut rows = Vec::new();
+        property.push_rows(empty_row(InventoryLevel::Property), &mut rows);
+        // Stands without a real estate are only counted in the property totals
+        for (real_estate_id, accumulator) in real_estates.iter().filter(|(id, _)| id.is_some()) {
+            let row = InventoryRow { real_estate_id: *real_estate_id, ..empty_row(InventoryLevel::RealEstate) };
+            accumulator.push_rows(row, &mut rows);
+        }
+        for ((real_estate_id, parcel_number), accumulator) in parcels.iter().filter(|((_, number), _)| number.is_some()) {
+            let row = InventoryRow {
+                real_estate_id: *real_estate_id,
+                parcel_number: *parcel_number,
+                ..empty_row(InventoryLevel::Parcel)
+            };
+            accumulator.push_rows(row, &mut rows);
+        }
+        rows.extend(stand_rows);
+
+        InventorySummary { rows }
+    }
+}
+
+#[test]
+fn test_inventory_summary() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let summary = property.inventory_summary();
+    let total = summary.property_total().unwrap();
+
+    // Units of each level add up to the property
+    for level in [InventoryLevel::RealEstate, InventoryLevel::Parcel, InventoryLevel::Stand] {
+        let volume: f64 = summary.rows_at(level).filter(|row| row.tree_species.is_none()).map(|row| row.totals.volume).sum();
+        assert!((volume - total.totals.volume).abs() < 1e-6 * total.totals.volume);
+    }
+    let species_volume: f64 = summary.rows_at(InventoryLevel::Property)
+        .filter(|row| row.tree_species.is_some())
+        .map(|row| row.totals.volume)
+        .sum();
+    assert!((species_volume - total.totals.volume).abs() < 1e-6 * total.totals.volume);
+    assert!((total.per_hectare.volume * total.area - total.totals.volume).abs() < 1e-6 * total.totals.volume);
+
+    let csv = summary.to_csv();
+    assert_eq!(csv.lines().count(), summary.rows.len() + 1);
+    assert!(csv.starts_with("level,real_estate_id,parcel_number"));
+}
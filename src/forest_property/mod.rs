@@ -4,4 +4,21 @@ pub mod forest_property_data;
 pub mod stand;
 pub mod geometry;
 pub mod tree_stand_data;
-pub mod compartment;
\ No newline at end of file
+pub mod compartment;
+pub mod xml_reader;
+pub mod stand_reader;
+pub mod xml_writer;
+pub mod schema_version;
+pub mod validation;
+pub mod codes;
+pub mod inventory;
+pub mod growth;
+pub mod harvest;
+pub mod cutting;
+pub mod diameter_distribution;
+pub mod crown;
+pub mod dead_wood;
+pub mod biomass;
+pub mod valuation;
+pub mod special_features;
+mod csv_utils;
@@ -0,0 +1,168 @@
+use std::fmt;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+
+// Namespace of the stand elements. MV1.7 introduced the dated revision of the namespace.
+const STAND_NAMESPACE: &str = "http://standardit.tapio.fi/schemas/forestData/stand";
+const DATED_STAND_NAMESPACE: &str = "http://standardit.tapio.fi/schemas/forestData/stand/2010/08/31";
+
+/// Revision of the Forest Data schema a document was written with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SchemaVersion {
+    /// Revisions before MV1.7, which use the undated stand namespace
+    Legacy,
+    Mv1_7,
+    Mv1_8,
+    Mv1_9,
+    /// The version could not be detected from the document
+    #[default]
+    Unknown,
+}
+
+impl SchemaVersion {
+    /// Parses a version string such as `MV1.7` or `1.8`.
+    pub fn parse(version: &str) -> Option<SchemaVersion> {
+        let version = version.trim();
+        let number = version.strip_prefix("MV").or_else(|| version.strip_prefix("mv")).unwrap_or(version);
+
+        match number {
+            "1.7" => Some(SchemaVersion::Mv1_7),
+            "1.8" => Some(SchemaVersion::Mv1_8),
+            "1.9" => Some(SchemaVersion::Mv1_9),
+            _ => match number.split_once('.') {
+                Some(("1", minor)) if minor.parse::<u8>().is_ok_and(|minor| minor < 7) => Some(SchemaVersion::Legacy),
+                _ => None,
+            },
+        }
+    }
+
+    /// Whether documents of the version can be read and written. MV1.8 and MV1.9 are detected
+    /// but their element changes are not mapped, so they are rejected instead of misread.
+    pub fn is_supported(&self) -> bool {
+        !matches!(self, SchemaVersion::Mv1_8 | SchemaVersion::Mv1_9)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SchemaVersion::Legacy => "legacy",
+            SchemaVersion::Mv1_7 => "MV1.7",
+            SchemaVersion::Mv1_8 => "MV1.8",
+            SchemaVersion::Mv1_9 => "MV1.9",
+            SchemaVersion::Unknown => "unknown",
+        }
+    }
+
+    /// Reads the version from a header comment, e.g. `Created by TAPIO ForestKIT ... SchemaVersio: MV1.7`.
+    pub fn from_comment(comment: &str) -> Option<SchemaVersion> {
+        let start = comment.find("SchemaVersio")?;
+        let rest = comment[start..].trim_start_matches(|c: char| c.is_alphabetic());
+        let rest = rest.trim_start_matches([':', '=', ' ']);
+        let value = rest.split_whitespace().next()?;
+
+        SchemaVersion::parse(value)
+    }
+
+    /// Oldest revision that uses the given stand namespace.
+    pub fn from_stand_namespace(namespace: &str) -> Option<SchemaVersion> {
+        match namespace.trim_end_matches('/') {
+            DATED_STAND_NAMESPACE => Some(SchemaVersion::Mv1_7),
+            STAND_NAMESPACE => Some(SchemaVersion::Legacy),
+            _ => None,
+        }
+    }
+
+    // Version from the namespace declarations of the root element
+    pub(crate) fn from_root_element(root: &BytesStart) -> SchemaVersion {
+        root.attributes()
+            .flatten()
+            .filter(|attribute| attribute.key.as_namespace_binding().is_some())
+            .find_map(|attribute| SchemaVersion::from_stand_namespace(&String::from_utf8_lossy(&attribute.value)))
+            .unwrap_or_default()
+    }
+
+    /// Detects the version of a document from its header comments, falling back
+    /// to the stand namespace declared on the root element.
+    pub fn detect(xml: &str) -> SchemaVersion {
+        let mut reader = Reader::from_str(xml);
+
+        loop {
+            match reader.read_event() {
+                Ok(Event::Comment(comment)) => {
+                    if let Some(version) = SchemaVersion::from_comment(&String::from_utf8_lossy(&comment)) {
+                        return version;
+                    }
+                }
+                Ok(Event::Start(root)) | Ok(Event::Empty(root)) => {
+                    return SchemaVersion::from_root_element(&root);
+                }
+                Ok(Event::Eof) | Err(_) => return SchemaVersion::Unknown,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[test]
+fn test_detect_schema_version() {
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    assert_eq!(SchemaVersion::detect(&xml), SchemaVersion::Mv1_7);
+
+    let xml = xml.replacen("SchemaVersio: MV1.7", "SchemaVersio: MV1.9", 1);
+    assert_eq!(SchemaVersion::detect(&xml), SchemaVersion::Mv1_9);
+
+    // Without a header comment the namespace decides
+    let xml = r#"<ForestPropertyData xmlns:st="http://standardit.tapio.fi/schemas/forestData/stand"/>"#;
+    assert_eq!(SchemaVersion::detect(xml), SchemaVersion::Legacy);
+    assert_eq!(SchemaVersion::detect("<ForestPropertyData/>"), SchemaVersion::Unknown);
+}
+
+#[test]
+fn test_reject_unsupported_schema_version() {
+    use super::forest_property_data::ForestPropertyData;
+    use super::stand_reader::StandReader;
+
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    let xml = xml.replacen("SchemaVersio: MV1.7", "SchemaVersio: MV1.8", 1);
+
+    let err = ForestPropertyData::try_parse_from_str(&xml).unwrap_err();
+    assert!(err.is_unsupported_version());
+    assert!(err.to_string().contains("MV1.8"));
+
+    let mut reader = StandReader::new(xml.as_bytes());
+    assert!(reader.next().unwrap().unwrap_err().is_unsupported_version());
+    assert!(reader.next().is_none());
+
+    let mut property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    property.schema_version = SchemaVersion::Mv1_9;
+    assert!(property.to_xml_string().unwrap_err().is_unsupported_version());
+}
+
+#[test]
+fn test_parse_stands_without_real_estates() {
+    use super::forest_property_data::ForestPropertyData;
+
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    let start = xml.find("<st:Stand id=").unwrap();
+    let end = xml.find("</st:Stand>").unwrap() + "</st:Stand>".len();
+    let xml = format!("<ForestPropertyData><st:Stands>{}</st:Stands></ForestPropertyData>", &xml[start..end]);
+
+    let property = ForestPropertyData::try_parse_from_str(&xml).unwrap();
+    assert!(property.real_estates.real_estate.is_empty());
+    assert_eq!(property.schema_version, SchemaVersion::Unknown);
+
+    let stands = property.get_stands();
+    assert_eq!(stands.len(), 1);
+    assert_eq!(stands[0].real_estate_id, None);
+
+    let written = property.to_xml_string().unwrap();
+    let round_tripped = ForestPropertyData::try_parse_from_str(&written).unwrap();
+    assert_eq!(round_tripped.schema_version, SchemaVersion::Mv1_7);
+    assert_eq!(round_tripped.stands, property.stands);
+}
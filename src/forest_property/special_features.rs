@@ -0,0 +1,148 @@
+use geojson::{FeatureCollection, GeoJson};
+use serde::{Deserialize, Serialize};
+use crate::geojson_utils::{compartment_properties, convert_polygon_to_feature};
+use super::forest_property_data::SpecialFeature;
+use super::stand::Stand;
+
+// Style of the special feature layer, in the properties of the simplestyle spec
+const STROKE_COLOR: &str = "#c0399b";
+const FILL_COLOR: &str = "#e07ac8";
+const FILL_OPACITY: f64 = 0.35;
+
+/// How a stand with a special feature is treated. Features have no geometry of their own
+/// in Forest Data, so a rule applies to the whole stand.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeatureRule {
+    /// `FeatureCode` the rule applies to
+    pub feature_code: String,
+    /// Cutting simulations leave the stand as it is
+    #[serde(default)]
+    pub no_removal: bool,
+    /// No living trees are generated for the stand
+    #[serde(default)]
+    pub skip_tree_generation: bool,
+}
+
+/// Rules of the special features. Features without a rule don't change anything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FeatureRules {
+    pub rules: Vec<FeatureRule>,
+}
+
+/// Combined rules of all special features of a stand
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StandZone {
+    pub no_removal: bool,
+    pub skip_tree_generation: bool,
+}
+
+impl FeatureRules {
+    pub fn new(rules: Vec<FeatureRule>) -> Self {
+        FeatureRules { rules }
+    }
+
+    // Stands with any of the codes are left uncut
+    pub fn no_removal(feature_codes: &[&str]) -> Self {
+        FeatureRules::new(
+            feature_codes
+                .iter()
+                .map(|code| FeatureRule { feature_code: code.to_string(), no_removal: true, skip_tree_generation: false })
+                .collect(),
+        )
+    }
+
+    pub fn zone(&self, stand: &Stand) -> StandZone {
+        stand.special_features()
+            .iter()
+            .flat_map(|feature| self.rules.iter().filter(move |rule| rule.feature_code == feature.feature_code.trim()))
+            .fold(StandZone::default(), |zone, rule| StandZone {
+                no_removal: zone.no_removal || rule.no_removal,
+                skip_tree_generation: zone.skip_tree_generation || rule.skip_tree_generation,
+            })
+    }
+}
+
+impl Stand {
+    pub fn has_special_feature(&self, feature_code: &str) -> bool {
+        self.special_features().iter().any(|feature| feature.feature_code.trim() == feature_code)
+    }
+}
+
+fn feature_properties(stand: &Stand, feature: &SpecialFeature) -> serde_json::Map<String, serde_json::Value> {
+    let mut properties = compartment_properties(
+        &stand.stand_basic_data.stand_number.to_string(),
+        stand.real_estate_id,
+        stand.parcel_number,
+    );
+    properties.insert("stand_id".to_string(), serde_json::json!(stand.id));
+    properties.insert("feature_id".to_string(), serde_json::json!(feature.id));
+    properties.insert("feature_code".to_string(), serde_json::json!(feature.feature_code.trim()));
+    properties.insert("feature_additional_code".to_string(), serde_json::json!(feature.feature_additional_code));
+    properties.insert("stroke".to_string(), serde_json::json!(STROKE_COLOR));
+    properties.insert("fill".to_string(), serde_json::json!(FILL_COLOR));
+    properties.insert("fill-opacity".to_string(), serde_json::json!(FILL_OPACITY));
+    properties
+}
+
+/// One feature per special feature and part of its stand, with the polygon of the part and a style of its own.
+/// `stands` must have their polygons computed.
+pub fn special_features_to_geojson<'a>(stands: impl IntoIterator<Item = &'a Stand>) -> GeoJson {
+    let features = stands
+        .into_iter()
+        .flat_map(|stand| stand.polygons().iter().map(move |polygon| (stand, polygon)))
+        .flat_map(|(stand, polygon)| {
+            stand.special_features()
+                .iter()
+                .map(move |feature| convert_polygon_to_feature(polygon, Some(feature_properties(stand, feature))))
+        })
+        .collect();
+
+    GeoJson::FeatureCollection(FeatureCollection {
+        features,
+        bbox: None,
+        foreign_members: None,
+    })
+}
+
+#[test]
+fn test_special_feature_zones() {
+    use super::compartment::GenerationOptions;
+    use super::cutting::RemovalSpec;
+    use super::forest_property_data::ForestPropertyData;
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stands = property.get_stands();
+    let stand = stands.iter().find(|stand| stand.has_special_feature("1011") && stand.get_strata().is_some()).unwrap();
+
+    let options = GenerationOptions { feature_rules: FeatureRules::no_removal(&["1011"]), ..Default::default() };
+    assert!(options.feature_rules.zone(stand).no_removal);
+    let result = stand.cut(&RemovalSpec::ClearCut, &options).unwrap();
+    assert!(result.removed.tree_stratum.is_empty());
+    assert_eq!(result.remaining, stand.get_strata().unwrap());
+
+    let GeoJson::FeatureCollection(collection) = special_features_to_geojson(&stands) else { panic!("Expected a feature collection") };
+    let feature_count: usize = stands.iter().map(|stand| stand.special_features().len() * stand.polygons().len()).sum();
+    assert_eq!(collection.features.len(), feature_count);
+}
+
+#[test]
+fn test_skip_tree_generation() {
+    use super::compartment::{GenerationOptions, StandTrees};
+    use super::forest_property_data::ForestPropertyData;
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stands = property.get_stands();
+    let stand = stands.iter()
+        .filter(|stand| stand.has_special_feature("1011") && stand.summary_stem_count().unwrap_or(0) > 0)
+        .min_by(|a, b| a.stand_basic_data.area.total_cmp(&b.stand_basic_data.area))
+        .unwrap();
+    assert!(!StandTrees::generate(stand, &GenerationOptions::default()).trees.is_empty());
+
+    // Both the native and the WASM path generate the trees with `StandTrees::generate`
+    let rule = FeatureRule { feature_code: "1011".to_string(), no_removal: false, skip_tree_generation: true };
+    let options = GenerationOptions { feature_rules: FeatureRules::new(vec![rule]), ..Default::default() };
+    assert!(options.feature_rules.zone(stand).skip_tree_generation);
+    let stand_trees = StandTrees::generate(stand, &options);
+    assert!(stand_trees.trees.is_empty());
+    assert!(stand_trees.fill_report.strata.is_empty());
+}
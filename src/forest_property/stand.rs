@@ -1,8 +1,8 @@
-use geo::{Coord, LineString, Polygon};
+use geo::{Area, Coord, LineString, MultiPolygon, Polygon};
 use serde::{Deserialize, Serialize};
-use crate::forest_property::tree_stand_data::TreeStrata;
-use crate::forest_property::forest_property_data::{ TreeStandDataDate, TreeStratum};
-use crate::forest_property::forest_property_data::{Operations, SpecialFeatures, StandBasicData, TreeStandData};
+use crate::forest_property::tree_stand_data::{DeadTreeStratum, SnapshotSelector, TreeStandData, TreeStandDataDate, TreeStrata, TreeStratum};
+use crate::forest_property::forest_property_data::{Operation, Operations, SpecialFeature, SpecialFeatures, StandBasicData};
+use crate::forest_property::geometry::{Coordinates, Polygon as GmlPolygon, PolygonGeometry};
 use crate::projection::{Projection, CRS};
 
 
@@ -30,130 +30,258 @@ pub struct Stand {
     pub operations: Option<Operations>,
     #[serde(rename = "TreeStandData")]
     pub tree_stand_data: Option<TreeStandData>,
+    // Largest polygon of the stand
     #[serde(skip_serializing, skip_deserializing)]
     pub computed_polygon: Option<Polygon>,
+    // All polygons of the stand, for stands made of several parts, see `polygons`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub computed_multi_polygon: Option<MultiPolygon>,
     #[serde(skip_serializing, skip_deserializing)]
     pub proj: Projection,
+    // Real estate and parcel of the stand, set when the stand is read from a property
+    #[serde(skip_serializing, skip_deserializing)]
+    pub real_estate_id: Option<u32>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub parcel_id: Option<u32>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub parcel_number: Option<i64>,
 }
 
 impl Stand {
+    // Both polygons are None if the stand has no polygon in a known reference system
     pub fn compute_polygon(&mut self) -> &Self {
 
-        self.computed_polygon = Some(self.create_polygon());
+        let multi_polygon = self.create_multi_polygon();
+        if multi_polygon.0.is_empty() {
+            self.computed_polygon = None;
+            self.computed_multi_polygon = None;
+        } else {
+            self.computed_polygon = Some(largest_polygon(&multi_polygon));
+            self.computed_multi_polygon = Some(multi_polygon);
+        }
         self
        
     }
 
-    pub fn parse_geometry(&self, coord_string: &String) -> Vec<Coord<f64>> {
-        let coordinates_str: Vec<&str> = coord_string.split(" ").collect();
-
-        // Parse coordinates into a Vec of `Coord<f64>`
-        let mut coords: Vec<Coord<f64>> = Vec::new();
-
-        for coordinate in coordinates_str {
-            let parts: Vec<&str> = coordinate.split(',').collect();
-            if parts.len() == 2 {
-                let e: f64 = parts[0].parse().expect("Invalid x coordinate");
-                let n: f64 = parts[1].parse().expect("Invalid y coordinate");
-
-                let (lon, lat) = self.proj.transform(e, n);
-                coords.push(Coord { x: lon, y: lat });
-            } else {
-                println!("Invalid coordinate format: {}", coordinate);
-            }
+    // Every part of the stand's computed polygon, empty if it has none
+    pub fn polygons(&self) -> &[Polygon] {
+        match &self.computed_multi_polygon {
+            Some(multi_polygon) => &multi_polygon.0,
+            None => &[],
         }
+    }
 
-        coords
+    pub fn parse_geometry(&self, coord_string: &str) -> Vec<Coord<f64>> {
+        let coordinates = Coordinates::new(coord_string);
+        transform_coords(&self.proj, coordinates.positions(), false)
     }
- 
+
+    // Rings of the largest polygon of the stand in WGS84
     pub fn get_geometries(&self) -> (LineString, Vec<LineString>) {
-        let polygon = &self
-            .stand_basic_data
-            .polygon_geometry
-            .polygon_property
-            .polygon;
-
-        let exterior = &polygon.exterior.linear_ring.coordinates;
-        let exterior_geometry = LineString::new(self.parse_geometry(&exterior).to_owned());
-
-        let interior_geometry: Vec<LineString> = polygon
-            .interior
-            .iter()
-            .map(|f| {
-                let geometry = self.parse_geometry(&f.linear_ring.coordinates);
-                LineString::new(geometry)
+        let polygon = match &self.computed_polygon {
+            Some(polygon) => polygon.to_owned(),
+            None => self.clone().create_polygon(),
+        };
+
+        let (exterior, interiors) = polygon.into_inner();
+        (exterior, interiors)
+    }
+
+    fn to_wgs84(&self, geometry: &PolygonGeometry) -> MultiPolygon {
+        let geometry_srs_name = geometry.srs_name();
+
+        // Polygons in an unknown reference system are left out, see `unknown_srs_names`
+        let polygons = geometry.polygons()
+            .into_iter()
+            .filter_map(|polygon| {
+                // Polygons can declare a reference system of their own
+                if polygon.srs_name.is_empty() || polygon.srs_name == geometry_srs_name {
+                    crs_from_srs_name(geometry_srs_name)?;
+                    Some(transform_polygon(&self.proj, polygon, geometry_srs_name))
+                } else {
+                    let projection = Projection::new(crs_from_srs_name(&polygon.srs_name)?, CRS::Epsg4326);
+                    Some(transform_polygon(&projection, polygon, &polygon.srs_name))
+                }
             })
             .collect();
 
-        (exterior_geometry, interior_geometry)
+        MultiPolygon::new(polygons)
     }
 
-    pub fn create_polygon(&mut self) -> Polygon {
-        // Projection from ETRS-TM35FIN to WGS84
-        self.proj = Projection::new(CRS::Epsg3067, CRS::Epsg4326);
+    // The projection is chosen by the srsName of the geometry, ETRS-TM35FIN if it has none
+    pub fn create_multi_polygon(&mut self) -> MultiPolygon {
+        self.proj = match crs_from_srs_name(self.stand_basic_data.polygon_geometry.srs_name()) {
+            Some(crs) => Projection::new(crs, CRS::Epsg4326),
+            None => Projection::default(),
+        };
+        self.to_wgs84(&self.stand_basic_data.polygon_geometry)
+    }
 
-        let (exterior, interior) = self.get_geometries();
+    // srsNames of the geometry that don't name a supported reference system
+    pub fn unknown_srs_names(&self) -> Vec<&str> {
+        let geometry = &self.stand_basic_data.polygon_geometry;
+        let mut srs_names: Vec<&str> = geometry.polygons().iter().map(|polygon| polygon.srs_name.as_str()).collect();
+        srs_names.push(geometry.srs_name());
 
-        let polygon = Polygon::new(exterior, interior);
+        let mut unknown: Vec<&str> = srs_names.into_iter().filter(|srs_name| crs_from_srs_name(srs_name).is_none()).collect();
+        unknown.sort_unstable();
+        unknown.dedup();
+        unknown
+    }
 
-        polygon
+    // Largest polygon of the stand
+    pub fn create_polygon(&mut self) -> Polygon {
+        largest_polygon(&self.create_multi_polygon())
     }
 
-    pub fn summary_stem_count(&self) -> Option<u32> {
+    pub fn operations(&self) -> &[Operation] {
+        match &self.operations {
+            Some(operations) => &operations.operation,
+            None => &[],
+        }
+    }
 
-        let last_data_date = match self.get_last_tree_stand_data_date() {
-            Some(data) => data,
-            None => return None 
-        };
+    pub fn special_features(&self) -> &[SpecialFeature] {
+        match &self.special_features {
+            Some(special_features) => &special_features.special_feature,
+            None => &[],
+        }
+    }
 
-        match &last_data_date.tree_stand_summary {
-            Some(summary) => return Some(summary.stem_count),
-            None => return None
-        };
-        
+    // Dead tree strata of the selected snapshot
+    pub fn dead_tree_strata_for(&self, selector: &SnapshotSelector) -> &[DeadTreeStratum] {
+        match self.select_tree_stand_data_date(selector).and_then(|data_date| data_date.dead_tree_strata.as_ref()) {
+            Some(dead_tree_strata) => &dead_tree_strata.dead_tree_stratum,
+            None => &[],
+        }
     }
 
-    pub fn stem_count_in_stratum(&self) -> bool {
-        let stratums = self.get_stratums();
+    // Dated tree stand data snapshots of the stand in file order
+    pub fn tree_stand_data_dates(&self) -> &[TreeStandDataDate] {
+        match &self.tree_stand_data {
+            Some(data) => &data.tree_stand_data_date,
+            None => &[],
+        }
+    }
 
-        let stratum_vec = match stratums {
-            Some(stratum) => stratum,
-            None => return false
-        };
+    pub fn select_tree_stand_data_date(&self, selector: &SnapshotSelector) -> Option<&TreeStandDataDate> {
+        self.tree_stand_data.as_ref()?.select(selector)
+    }
 
-        true
+    pub fn summary_stem_count(&self) -> Option<u32> {
+        self.summary_stem_count_for(&SnapshotSelector::default())
     }
 
-    pub fn get_stratums(&self) -> Option<Vec<TreeStratum>> {
-        let last_data_date = match self.get_last_tree_stand_data_date() {
-            Some(data) => data,
-            None => return None 
-        };
+    pub fn summary_stem_count_for(&self, selector: &SnapshotSelector) -> Option<u32> {
+        let data_date = self.select_tree_stand_data_date(selector)?;
+        data_date.tree_stand_summary.as_ref().map(|summary| summary.stem_count)
+    }
 
-        let stratums = last_data_date.tree_strata.tree_stratum.to_owned();
-        Some(stratums)
+    pub fn stem_count_in_stratum(&self) -> bool {
+        self.get_stratums().is_some()
+    }
+
+    pub fn get_stratums(&self) -> Option<Vec<TreeStratum>> {
+        let data_date = self.select_tree_stand_data_date(&SnapshotSelector::default())?;
+        Some(data_date.tree_strata.tree_stratum.to_owned())
     }
 
     pub fn get_strata(&self) -> Option<TreeStrata> {
-        let last_data_date = match self.get_last_tree_stand_data_date() {
-            Some(data) => data,
-            None => return None 
-        };
+        self.get_strata_for(&SnapshotSelector::default())
+    }
 
-        let strata = &last_data_date.tree_strata.tree_stratum;
-        let strata = TreeStrata::new(strata.to_vec());
-        Some(strata)
+    pub fn get_strata_for(&self, selector: &SnapshotSelector) -> Option<TreeStrata> {
+        let data_date = self.select_tree_stand_data_date(selector)?;
+        Some(TreeStrata::new(data_date.tree_strata.tree_stratum.to_vec()))
     }
 
+    // Latest snapshot by date, see `SnapshotSelector`
     pub fn get_last_tree_stand_data_date(&self) -> Option<TreeStandDataDate> {
-        let stand_data = match &self.tree_stand_data {
-            Some(data) => data,
-            None => return None 
-        };
+        self.select_tree_stand_data_date(&SnapshotSelector::default()).cloned()
+    }
+}
 
-        match stand_data.tree_stand_data_date.last() {
-            Some(last_data_date) => Some(last_data_date.to_owned()),
-            None => return None 
-        }
+// Projects coordinates to WGS84. `latitude_first` swaps the axes of lat/lon ordered input.
+fn transform_coords(proj: &Projection, coords: Vec<Coord<f64>>, latitude_first: bool) -> Vec<Coord<f64>> {
+    coords.into_iter().map(|coord| {
+        let (e, n) = if latitude_first { (coord.y, coord.x) } else { (coord.x, coord.y) };
+        let (lon, lat) = proj.transform(e, n);
+        Coord { x: lon, y: lat }
+    }).collect()
+}
+
+fn transform_polygon(proj: &Projection, polygon: &GmlPolygon, srs_name: &str) -> Polygon {
+    let (exterior, interiors) = polygon.rings();
+    let latitude_first = CRS::has_latitude_first(srs_name);
+
+    let exterior = LineString::new(transform_coords(proj, exterior.0, latitude_first));
+    let interiors = interiors
+        .into_iter()
+        .map(|interior| LineString::new(transform_coords(proj, interior.0, latitude_first)))
+        .collect();
+
+    Polygon::new(exterior, interiors)
+}
+
+// Only a missing srsName defaults to ETRS-TM35FIN
+fn crs_from_srs_name(srs_name: &str) -> Option<CRS> {
+    if srs_name.trim().is_empty() {
+        Some(CRS::Epsg3067)
+    } else {
+        CRS::from_srs_name(srs_name)
     }
 }
+
+fn largest_polygon(multi_polygon: &MultiPolygon) -> Polygon {
+    multi_polygon.0.iter()
+        .max_by(|a, b| a.unsigned_area().total_cmp(&b.unsigned_area()))
+        .cloned()
+        .unwrap_or_else(|| Polygon::new(LineString::new(vec![]), vec![]))
+}
+
+#[test]
+fn test_multi_polygon_stand() {
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    let start = xml.find("<st:Stand id=").unwrap();
+    let end = xml.find("</st:Stand>").unwrap() + "</st:Stand>".len();
+    let stand_xml = &xml[start..end];
+
+    let geometry_start = stand_xml.find("<gml:polygonProperty>").unwrap();
+    let geometry_end = stand_xml.find("</gml:polygonProperty>").unwrap() + "</gml:polygonProperty>".len();
+    let multi_polygon = r#"<gml:multiPolygonProperty><gml:MultiPolygon srsName="EPSG:3067" srsDimension="3">
+        <gml:polygonMember><gml:Polygon><gml:exterior><gml:LinearRing>
+            <gml:posList>427000 7372000 0 427100 7372000 0 427100 7372100 0 427000 7372000 0</gml:posList>
+        </gml:LinearRing></gml:exterior></gml:Polygon></gml:polygonMember>
+        <gml:polygonMember><gml:Polygon><gml:exterior><gml:LinearRing>
+            <gml:posList>428000 7372000 0 428010 7372000 0 428010 7372010 0 428000 7372000 0</gml:posList>
+        </gml:LinearRing></gml:exterior></gml:Polygon></gml:polygonMember>
+    </gml:MultiPolygon></gml:multiPolygonProperty>"#;
+    let stand_xml = format!("{}{}{}", &stand_xml[..geometry_start], multi_polygon, &stand_xml[geometry_end..]);
+
+    let mut stand: Stand = quick_xml::de::from_str(&stand_xml).unwrap();
+    stand.compute_polygon();
+
+    let multi_polygon = stand.computed_multi_polygon.as_ref().unwrap();
+    assert_eq!(multi_polygon.0.len(), 2);
+    assert_eq!(stand.computed_polygon.as_ref(), Some(&multi_polygon.0[0]));
+    assert_eq!(stand.polygons(), multi_polygon.0.as_slice());
+
+    let first = multi_polygon.0[0].exterior().0[0];
+    let (lon, lat) = Projection::new(CRS::Epsg3067, CRS::Epsg4326).transform(427000.0, 7372000.0);
+    assert!((first.x - lon).abs() < 1E-9 && (first.y - lat).abs() < 1E-9);
+}
+
+#[test]
+fn test_unknown_srs_name_has_no_polygon() {
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    let start = xml.find("<st:Stand id=").unwrap();
+    let end = xml.find("</st:Stand>").unwrap() + "</st:Stand>".len();
+
+    let mut stand: Stand = quick_xml::de::from_str(&xml[start..end]).unwrap();
+    stand.stand_basic_data.polygon_geometry.polygon_property.polygon.as_mut().unwrap().srs_name = "EPSG:9999".to_string();
+    stand.compute_polygon();
+
+    assert!(stand.computed_polygon.is_none());
+    assert!(stand.computed_multi_polygon.is_none());
+    assert!(stand.polygons().is_empty());
+}
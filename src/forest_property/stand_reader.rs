@@ -0,0 +1,395 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use crate::error::ForestDataError;
+use super::schema_version::SchemaVersion;
+use super::stand::Stand;
+use super::xml_reader::{decode_with_encoding, find_failing_element};
+
+/// Real estate fields that precede its parcels in the document
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RealEstateInfo {
+    pub id: u32,
+    pub municipality_number: u16,
+    pub area_number: u16,
+    pub group_number: u16,
+    pub unit_number: u16,
+    pub real_estate_name: String,
+}
+
+/// Parcel fields that precede its stands in the document
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ParcelInfo {
+    pub id: u32,
+    pub parcel_number: i64,
+}
+
+/// Stand read from the document together with the real estate and parcel it belongs to
+#[derive(Debug, Clone)]
+pub struct StandRecord {
+    pub real_estate: RealEstateInfo,
+    pub parcel: ParcelInfo,
+    pub stand: Stand,
+}
+
+// BufRead wrapper that keeps track of line numbers of the consumed input
+struct LineCounter<R: BufRead> {
+    inner: R,
+    consumed: u64,
+    line: usize,
+    line_start: u64,
+}
+
+impl<R: BufRead> Read for LineCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for LineCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            for (i, &b) in buf[..amount.min(buf.len())].iter().enumerate() {
+                if b == b'\n' {
+                    self.line += 1;
+                    self.line_start = self.consumed + i as u64 + 1;
+                }
+            }
+        }
+        self.consumed += amount as u64;
+        self.inner.consume(amount);
+    }
+}
+
+/// Event based reader that yields the stands of a Forest Data document one at a time,
+/// so that documents larger than the available memory can be processed.
+pub struct StandReader<R: BufRead> {
+    reader: Reader<LineCounter<R>>,
+    buf: Vec<u8>,
+    encoding: String,
+    schema_version: SchemaVersion,
+    // Local names of the open elements
+    path: Vec<String>,
+    real_estate: RealEstateInfo,
+    parcel: ParcelInfo,
+    real_estate_index: usize,
+    parcel_index: usize,
+    stand_index: usize,
+    done: bool,
+}
+
+impl StandReader<BufReader<File>> {
+    pub fn from_file(path: &str) -> Result<Self, ForestDataError> {
+        let file = File::open(path)?;
+        Ok(StandReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> StandReader<R> {
+    pub fn new(reader: R) -> Self {
+        let counter = LineCounter { inner: reader, consumed: 0, line: 1, line_start: 0 };
+
+        StandReader {
+            reader: Reader::from_reader(counter),
+            buf: Vec::new(),
+            encoding: "utf-8".to_string(),
+            schema_version: SchemaVersion::Unknown,
+            path: Vec::new(),
+            real_estate: RealEstateInfo::default(),
+            parcel: ParcelInfo::default(),
+            real_estate_index: 0,
+            parcel_index: 0,
+            stand_index: 0,
+            done: false,
+        }
+    }
+
+    /// Schema version of the document, known once the root element has been read
+    pub fn schema_version(&self) -> SchemaVersion {
+        self.schema_version
+    }
+
+    /// Yields only the stands, without their real estate and parcel context
+    pub fn stands(self) -> impl Iterator<Item = Result<Stand, ForestDataError>> {
+        self.map(|record| record.map(|r| r.stand))
+    }
+
+    // Element path of the current position, e.g. `RealEstates/RealEstate[1]/Parcels/Parcel[3]/Stands/Stand[17]`
+    fn element_path(&self) -> String {
+        self.path.iter().skip(1).map(|name| match name.as_str() {
+            "RealEstate" => format!("RealEstate[{}]", self.real_estate_index),
+            "Parcel" => format!("Parcel[{}]", self.parcel_index),
+            "Stand" => format!("Stand[{}]", self.stand_index),
+            _ => name.to_owned(),
+        }).collect::<Vec<String>>().join("/")
+    }
+
+    fn error_here(&self, position: u64, message: String) -> ForestDataError {
+        let counter = self.reader.get_ref();
+        ForestDataError::Schema {
+            path: self.element_path(),
+            position,
+            line: counter.line,
+            column: (position.saturating_sub(counter.line_start) + 1) as usize,
+            message,
+        }
+    }
+
+    fn attribute_id(&self, e: &BytesStart) -> Result<u32, ForestDataError> {
+        let position = self.reader.buffer_position();
+        match e.try_get_attribute("id") {
+            Ok(Some(attribute)) => String::from_utf8_lossy(&attribute.value).parse()
+                .map_err(|err| self.error_here(position, format!("Invalid id attribute: {}", err))),
+            Ok(None) => Ok(0),
+            Err(err) => Err(self.error_here(position, err.to_string())),
+        }
+    }
+
+    // Stores the text of a real estate or parcel field
+    fn read_field(&mut self, text: &str) -> Result<(), ForestDataError> {
+        let position = self.reader.buffer_position();
+        let invalid = |err: &dyn std::fmt::Display| format!("Invalid value '{}': {}", text, err);
+        let parent = self.path.iter().rev().nth(1).map(String::as_str);
+
+        match (parent, self.path.last().map(String::as_str)) {
+            (Some("RealEstate"), Some(field)) => {
+                let number = || text.parse::<u16>();
+                let real_estate = &mut self.real_estate;
+                let result = match field {
+                    "MunicipalityNumber" => number().map(|n| real_estate.municipality_number = n),
+                    "AreaNumber" => number().map(|n| real_estate.area_number = n),
+                    "GroupNumber" => number().map(|n| real_estate.group_number = n),
+                    "UnitNumber" => number().map(|n| real_estate.unit_number = n),
+                    "RealEstateName" => {
+                        real_estate.real_estate_name = text.to_string();
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                };
+                result.map_err(|err| self.error_here(position, invalid(&err)))
+            }
+            (Some("Parcel"), Some("ParcelNumber")) => {
+                self.parcel.parcel_number = text.parse()
+                    .map_err(|err| self.error_here(position, invalid(&err)))?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Copies the events of the stand element into a separate document and deserializes it
+    fn read_stand(&mut self, start: BytesStart<'static>) -> Result<Stand, ForestDataError> {
+        let start_position = self.reader.buffer_position() - start.len() as u64 - 2;
+        let start_line = self.reader.get_ref().line;
+        let start_column = (start_position.saturating_sub(self.reader.get_ref().line_start) + 1) as usize;
+
+        let mut writer = Writer::new(Vec::new());
+        let write_error = |err: quick_xml::Error| ForestDataError::Io(io::Error::other(err));
+        writer.write_event(Event::Start(start)).map_err(write_error)?;
+
+        let mut depth = 1;
+        while depth > 0 {
+            self.buf.clear();
+            let event = match self.reader.read_event_into(&mut self.buf) {
+                Ok(event) => event.into_owned(),
+                Err(err) => return Err(self.error_here(self.reader.error_position(), err.to_string())),
+            };
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                Event::Eof => return Err(self.error_here(self.reader.buffer_position(), "Unexpected end of document".to_string())),
+                _ => {}
+            }
+            writer.write_event(event).map_err(write_error)?;
+        }
+
+        let bytes = writer.into_inner();
+        let fragment = decode_with_encoding(&bytes, &self.encoding)?;
+
+        let mut stand: Stand = match quick_xml::de::from_str(&fragment) {
+            Ok(stand) => stand,
+            Err(err) => {
+                let path = self.element_path();
+                return Err(match find_failing_element(&fragment, err) {
+                    Ok(failing) => {
+                        let before = &fragment.as_bytes()[..failing.position];
+                        let lines = before.iter().filter(|&&b| b == b'\n').count();
+                        let column = match before.iter().rposition(|&b| b == b'\n') {
+                            Some(i) => failing.position - i,
+                            None => start_column + failing.position,
+                        };
+                        let mut segments = vec![path];
+                        segments.extend(failing.segments);
+
+                        ForestDataError::Schema {
+                            path: segments.join("/"),
+                            position: start_position + failing.position as u64,
+                            line: start_line + lines,
+                            column,
+                            message: failing.message,
+                        }
+                    }
+                    Err(syntax_error) => syntax_error,
+                });
+            }
+        };
+
+        stand.compute_polygon();
+        Ok(stand)
+    }
+
+    fn next_record(&mut self) -> Result<Option<StandRecord>, ForestDataError> {
+        loop {
+            self.buf.clear();
+            let event = match self.reader.read_event_into(&mut self.buf) {
+                Ok(event) => event.into_owned(),
+                Err(err) => return Err(self.error_here(self.reader.error_position(), err.to_string())),
+            };
+
+            match event {
+                Event::Decl(decl) => {
+                    if let Some(Ok(encoding)) = decl.encoding() {
+                        self.encoding = String::from_utf8_lossy(&encoding).to_lowercase();
+                    }
+                }
+                Event::Comment(comment) if self.path.is_empty() && self.schema_version == SchemaVersion::Unknown => {
+                    let comment = String::from_utf8_lossy(&comment).into_owned();
+                    self.schema_version = SchemaVersion::from_comment(&comment).unwrap_or_default();
+                }
+                Event::Start(e) => {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+
+                    if self.path.is_empty() && self.schema_version == SchemaVersion::Unknown {
+                        self.schema_version = SchemaVersion::from_root_element(&e);
+                    }
+                    if self.path.is_empty() && !self.schema_version.is_supported() {
+                        return Err(ForestDataError::UnsupportedVersion(self.schema_version));
+                    }
+
+                    match name.as_str() {
+                        "RealEstate" => {
+                            self.real_estate_index += 1;
+                            self.parcel_index = 0;
+                            self.real_estate = RealEstateInfo { id: self.attribute_id(&e)?, ..Default::default() };
+                        }
+                        "Parcel" => {
+                            self.parcel_index += 1;
+                            self.stand_index = 0;
+                            self.parcel = ParcelInfo { id: self.attribute_id(&e)?, ..Default::default() };
+                        }
+                        "Stand" => {
+                            self.stand_index += 1;
+                            self.path.push(name);
+                            let stand = self.read_stand(e);
+                            self.path.pop();
+
+                            // Newer revisions allow stands outside of real estates
+                            let in_parcel = self.path.iter().any(|name| name == "Parcel");
+                            return stand.map(|mut stand| {
+                                if in_parcel {
+                                    stand.real_estate_id = Some(self.real_estate.id);
+                                    stand.parcel_id = Some(self.parcel.id);
+                                    stand.parcel_number = Some(self.parcel.parcel_number);
+                                }
+                                Some(StandRecord {
+                                    real_estate: self.real_estate.clone(),
+                                    parcel: self.parcel.clone(),
+                                    stand,
+                                })
+                            });
+                        }
+                        _ => {}
+                    }
+                    self.path.push(name);
+                }
+                Event::Text(e) => {
+                    let raw = e.into_inner();
+                    let decoded = decode_with_encoding(&raw, &self.encoding)?;
+                    let text = quick_xml::escape::unescape(&decoded)
+                        .map_err(|err| self.error_here(self.reader.buffer_position(), err.to_string()))?;
+                    let text = text.trim();
+                    if !text.is_empty() {
+                        self.read_field(text)?;
+                    }
+                }
+                Event::End(_) => {
+                    self.path.pop();
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for StandReader<R> {
+    type Item = Result<StandRecord, ForestDataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                // The reader cannot recover its position after an error
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stand_reader_matches_parser() {
+    use super::forest_property_data::ForestPropertyData;
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stands = property.real_estates.real_estate[0].get_stands();
+
+    let mut reader = StandReader::from_file("forestpropertydata.xml").unwrap();
+    let first = reader.next().unwrap().unwrap();
+    assert_eq!(reader.schema_version(), property.schema_version);
+
+    let mut records: Vec<StandRecord> = vec![first];
+    records.extend(reader.collect::<Result<Vec<_>, _>>().unwrap());
+
+    assert_eq!(records.len(), stands.len());
+    for (record, stand) in records.iter().zip(stands.iter()) {
+        assert_eq!(record.stand.id, stand.id);
+        assert_eq!(record.stand.computed_polygon, stand.computed_polygon);
+        assert_eq!(record.stand.parcel_id, stand.parcel_id);
+        assert_eq!(record.real_estate.id, 526637);
+        assert_eq!(record.real_estate.municipality_number, 698);
+    }
+    assert_eq!(records.last().unwrap().parcel.id, property.real_estates.real_estate[0].parcels.parcel.last().unwrap().id);
+}
+
+#[test]
+fn test_stand_reader_reports_stand_path() {
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    let xml = xml.replacen("<st:StandNumber>1110</st:StandNumber>", "<st:StandNumber>x</st:StandNumber>", 1);
+
+    let result: Result<Vec<Stand>, ForestDataError> = StandReader::new(xml.as_bytes()).stands().collect();
+    let error = result.unwrap_err();
+
+    assert_eq!(error.path(), Some("RealEstates/RealEstate[1]/Parcels/Parcel[1]/Stands/Stand[2]/StandBasicData"));
+    match error {
+        ForestDataError::Schema { line, column, .. } => assert_eq!((line, column), (220, 15)),
+        _ => unreachable!(),
+    }
+}
@@ -1,24 +1,68 @@
+use crate::forest_property::codes::TreeSpecies;
+use crate::forest_property::crown::{Crown, CrownShape};
+
 #[derive(Default, Debug, Clone, Copy)]
 pub struct Tree {
-    species: u8,
+    species: TreeSpecies,
     mean_height: f32,
     position: (f64, f64, f64),
+    // Diameter at breast height in cm and height in m, zero if not generated
+    diameter: f32,
+    height: f32,
 }
 
 impl Tree {
-    pub fn new(species: u8, mean_height: f32, position: (f64, f64, f64)) -> Self {
+    pub fn new(species: TreeSpecies, mean_height: f32, position: (f64, f64, f64)) -> Self {
         Tree {
             species,
             mean_height,
             position,
+            diameter: 0.0,
+            height: mean_height,
         }
     }
 
-    pub fn species(&self) -> u8 {
+    pub fn with_dimensions(mut self, diameter: f32, height: f32) -> Self {
+        self.diameter = diameter;
+        self.height = height;
+        self
+    }
+
+    pub fn species(&self) -> TreeSpecies {
         self.species
     }
 
+    // Mean height of the stratum the tree was generated from
+    pub fn mean_height(&self) -> f32 {
+        self.mean_height
+    }
+
+    pub fn diameter(&self) -> f32 {
+        self.diameter
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    // Crown from the species and dimensions of the tree, see `Crown::of_tree`
+    pub fn crown(&self) -> Crown {
+        Crown::of_tree(self.species, self.diameter, self.height)
+    }
+
+    pub fn crown_radius(&self) -> f32 {
+        self.crown().radius
+    }
+
+    pub fn crown_base_height(&self) -> f32 {
+        self.crown().base_height
+    }
+
+    pub fn crown_shape(&self) -> CrownShape {
+        self.crown().shape
+    }
+
     pub fn position(&self) -> (f64, f64, f64) {
         self.position
     }
-}
\ No newline at end of file
+}
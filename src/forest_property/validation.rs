@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::fmt;
+use geo::{Coord, GeodesicArea, Line, LineString};
+use geo::orient::{Direction, Orient};
+use geo::line_intersection::{line_intersection, LineIntersection};
+use serde::Serialize;
+use super::forest_property_data::ForestPropertyData;
+use super::stand::Stand;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    UnclosedRing,
+    TooFewPoints,
+    SelfIntersectingRing,
+    MissingGeometry,
+    AreaMismatch,
+    StemCountMismatch,
+    MissingTreeStandData,
+    DuplicateStandNumber,
+    UnknownReferenceSystem,
+    InvalidCoordinate,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The stand cannot be used, e.g. its polygon is broken
+    Error,
+    /// The stand can be used but its data is inconsistent
+    Warning,
+}
+
+impl IssueKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            IssueKind::UnclosedRing
+            | IssueKind::TooFewPoints
+            | IssueKind::SelfIntersectingRing
+            | IssueKind::MissingGeometry
+            | IssueKind::UnknownReferenceSystem
+            | IssueKind::InvalidCoordinate => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    pub severity: Severity,
+    pub stand_id: String,
+    pub stand_number: String,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+    pub message: String,
+}
+
+/// Relative tolerances of the consistency checks
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationOptions {
+    /// Allowed difference between the polygon area and `StandBasicData.area`
+    pub area_tolerance: f64,
+    /// Allowed difference between the stem count of the strata and the summary
+    pub stem_count_tolerance: f64,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            area_tolerance: 0.05,
+            stem_count_tolerance: 0.05,
+        }
+    }
+}
+
+/// Result of `ForestPropertyData::validate`. Serializes to JSON for machine use,
+/// `summary()` gives a human readable version.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub stand_count: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// True if there are no errors. Warnings are allowed.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|issue| issue.kind == kind).count()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Validated {} stands: {} errors, {} warnings\n",
+            self.stand_count,
+            self.errors().count(),
+            self.warnings().count()
+        );
+
+        for issue in &self.issues {
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            summary.push_str(&format!(
+                "  {}: stand {} (id {}): {}\n",
+                severity, issue.stand_number, issue.stand_id, issue.message
+            ));
+        }
+
+        summary
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.summary())
+    }
+}
+
+impl ForestPropertyData {
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with_options(&ValidationOptions::default())
+    }
+
+    pub fn validate_with_options(&self, options: &ValidationOptions) -> ValidationReport {
+        let stands = self.get_stands();
+        let mut report = ValidationReport {
+            stand_count: stands.len(),
+            issues: Vec::new(),
+        };
+
+        for stand in &stands {
+            validate_stand(stand, options, &mut report.issues);
+        }
+        find_duplicate_stand_numbers(&stands, &mut report.issues);
+
+        report
+    }
+}
+
+fn issue(stand: &Stand, kind: IssueKind, message: String) -> ValidationIssue {
+    ValidationIssue {
+        kind,
+        severity: kind.severity(),
+        stand_id: stand.id.to_owned(),
+        stand_number: stand_number(stand),
+        real_estate_id: stand.real_estate_id,
+        parcel_number: stand.parcel_number,
+        message,
+    }
+}
+
+fn stand_number(stand: &Stand) -> String {
+    let basic_data = &stand.stand_basic_data;
+    format!("{}{}", basic_data.stand_number, basic_data.stand_number_extension)
+}
+
+// Checks a single stand. `stand` must have its polygon computed.
+pub fn validate_stand(stand: &Stand, options: &ValidationOptions, issues: &mut Vec<ValidationIssue>) {
+    let polygons = stand.stand_basic_data.polygon_geometry.polygons();
+    if polygons.is_empty() {
+        issues.push(issue(stand, IssueKind::MissingGeometry, "Stand has no polygon".to_string()));
+    }
+
+    let unknown_srs_names = stand.unknown_srs_names();
+    for srs_name in &unknown_srs_names {
+        let message = format!("Unknown reference system {}, the polygons in it are left out", srs_name);
+        issues.push(issue(stand, IssueKind::UnknownReferenceSystem, message));
+    }
+
+    // Rings are checked in the coordinates of the file, before projection
+    let mut rings_valid = !polygons.is_empty() && unknown_srs_names.is_empty();
+    for polygon in polygons {
+        for coordinate in polygon.invalid_coordinates() {
+            rings_valid = false;
+            issues.push(issue(stand, IssueKind::InvalidCoordinate, format!("Invalid coordinate {}", coordinate)));
+        }
+
+        let (exterior, interiors) = polygon.rings();
+        for (i, ring) in [exterior].iter().chain(interiors.iter()).enumerate() {
+            let ring_name = if i == 0 { "Exterior ring".to_string() } else { format!("Interior ring {}", i) };
+            for (kind, message) in check_ring(ring) {
+                rings_valid = false;
+                issues.push(issue(stand, kind, format!("{} {}", ring_name, message)));
+            }
+        }
+    }
+
+    let area = stand.stand_basic_data.area as f64;
+    if let (true, Some(multi_polygon)) = (rings_valid, &stand.computed_multi_polygon) {
+        // Geodesic area depends on the winding order of the rings
+        let polygon_area = multi_polygon.orient(Direction::Default).geodesic_area_unsigned() / 10_000.0;
+        if area > 0.0 && (polygon_area - area).abs() / area > options.area_tolerance {
+            let message = format!("Polygon area {:.4} ha differs from stand area {:.4} ha", polygon_area, area);
+            issues.push(issue(stand, IssueKind::AreaMismatch, message));
+        }
+    }
+
+    let data_dates = stand.tree_stand_data.as_ref()
+        .map(|data| data.tree_stand_data_date.as_slice())
+        .unwrap_or_default();
+    if data_dates.is_empty() {
+        issues.push(issue(stand, IssueKind::MissingTreeStandData, "Stand has no tree stand data".to_string()));
+    }
+
+    for data_date in data_dates {
+        let summary = match &data_date.tree_stand_summary {
+            Some(summary) => summary,
+            None => continue,
+        };
+        // Stem count is optional in the strata, the sum is only comparable if every stratum has one
+        let strata = &data_date.tree_strata.tree_stratum;
+        if strata.iter().any(|stratum| stratum.stem_count == 0) {
+            continue;
+        }
+        let strata_stem_count: u32 = strata.iter().map(|stratum| stratum.stem_count).sum();
+        let summary_stem_count = summary.stem_count as f64;
+        let difference = (strata_stem_count as f64 - summary_stem_count).abs();
+
+        if difference > 0.0 && difference > summary_stem_count * options.stem_count_tolerance {
+            let message = format!(
+                "Strata stem count {} differs from summary stem count {} (data of {}, type {})",
+                strata_stem_count, summary.stem_count, data_date.date, data_date.tree_stand_data_date_type.code()
+            );
+            issues.push(issue(stand, IssueKind::StemCountMismatch, message));
+        }
+    }
+}
+
+// Stand numbers have to be unique within a parcel
+fn find_duplicate_stand_numbers(stands: &[Stand], issues: &mut Vec<ValidationIssue>) {
+    let mut seen: HashMap<(Option<u32>, Option<i64>, String), &Stand> = HashMap::new();
+
+    for stand in stands {
+        let key = (stand.real_estate_id, stand.parcel_number, stand_number(stand));
+        if let Some(first) = seen.get(&key) {
+            let message = format!("Stand number {} is also used by stand {}", key.2, first.id);
+            issues.push(issue(stand, IssueKind::DuplicateStandNumber, message));
+        } else {
+            seen.insert(key, stand);
+        }
+    }
+}
+
+// Returns the problems of a polygon ring
+fn check_ring(ring: &LineString<f64>) -> Vec<(IssueKind, String)> {
+    let coords = &ring.0;
+    let mut problems = Vec::new();
+
+    // A closed ring needs at least three distinct points and the closing point
+    if coords.len() < 4 {
+        problems.push((IssueKind::TooFewPoints, format!("has {} points, at least 4 are needed", coords.len())));
+        return problems;
+    }
+    if coords.first() != coords.last() {
+        problems.push((IssueKind::UnclosedRing, "is not closed".to_string()));
+    }
+    if let Some(at) = find_self_intersection(coords) {
+        problems.push((IssueKind::SelfIntersectingRing, format!("intersects itself at ({}, {})", at.x, at.y)));
+    }
+
+    problems
+}
+
+// Finds a point where two segments of the ring cross or overlap. Adjacent segments may share their end point.
+fn find_self_intersection(coords: &[Coord<f64>]) -> Option<Coord<f64>> {
+    let lines: Vec<Line<f64>> = coords.windows(2)
+        .map(|pair| Line::new(pair[0], pair[1]))
+        .filter(|line| line.start != line.end)
+        .collect();
+    let closed = coords.first() == coords.last();
+
+    for i in 0..lines.len() {
+        for j in (i + 1)..lines.len() {
+            let adjacent = j == i + 1 || (closed && i == 0 && j == lines.len() - 1);
+
+            match line_intersection(lines[i], lines[j]) {
+                Some(LineIntersection::SinglePoint { intersection, .. }) if !adjacent => return Some(intersection),
+                Some(LineIntersection::Collinear { intersection }) if !adjacent || intersection.start != intersection.end => {
+                    return Some(intersection.start)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn test_validate_property() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let report = property.validate();
+
+    assert_eq!(report.stand_count, property.get_stands().len());
+    assert!(report.is_valid(), "{}", report);
+    assert!(report.to_json().unwrap().contains("\"stand_count\""));
+
+    // Break the ring of the first stand and duplicate its stand number
+    let mut broken = property.clone();
+    let stands = &mut broken.real_estates.real_estate[0].parcels.parcel[0].stands.stand;
+    let polygon = stands[0].stand_basic_data.polygon_geometry.polygon_property.polygon.as_mut().unwrap();
+    let coordinates = polygon.exterior.linear_ring.coordinates.as_mut().unwrap();
+    coordinates.value = "0,0 10,10 10,0 0,10 0,0".to_string();
+    stands[1].stand_basic_data.stand_number = stands[0].stand_basic_data.stand_number;
+    stands[1].stand_basic_data.stand_number_extension = stands[0].stand_basic_data.stand_number_extension.to_owned();
+    stands[1].tree_stand_data = None;
+
+    // An unknown srsName is not projected as ETRS-TM35FIN
+    let geometry = &mut stands[2].stand_basic_data.polygon_geometry;
+    geometry.polygon_property.polygon.as_mut().unwrap().srs_name = "EPSG:9999".to_string();
+    geometry.point_property.point.srs_name = "EPSG:9999".to_string();
+    assert!(stands[2].create_multi_polygon().0.is_empty());
+
+    let coordinates = stands[3].stand_basic_data.polygon_geometry.polygon_property.polygon.as_mut().unwrap()
+        .exterior.linear_ring.coordinates.as_mut().unwrap();
+    coordinates.value.push_str(" 1,x");
+
+    let report = broken.validate();
+    assert!(!report.is_valid());
+    assert_eq!(report.count(IssueKind::SelfIntersectingRing), 1);
+    assert_eq!(report.count(IssueKind::DuplicateStandNumber), 1);
+    assert!(report.count(IssueKind::MissingTreeStandData) >= 1);
+    assert_eq!(report.count(IssueKind::UnknownReferenceSystem), 1);
+    assert_eq!(report.count(IssueKind::InvalidCoordinate), 1);
+    assert!(report.summary().contains("EPSG:9999"));
+    assert!(report.summary().contains("1,x"));
+}
+
+#[test]
+fn test_check_ring() {
+    let ring = |coords: &[(f64, f64)]| LineString::from(coords.to_vec());
+
+    assert!(check_ring(&ring(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)])).is_empty());
+    assert_eq!(check_ring(&ring(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]))[0].0, IssueKind::UnclosedRing);
+    assert_eq!(check_ring(&ring(&[(0.0, 0.0), (1.0, 0.0), (0.0, 0.0)]))[0].0, IssueKind::TooFewPoints);
+    assert_eq!(check_ring(&ring(&[(0.0, 0.0), (2.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)]))[0].0, IssueKind::SelfIntersectingRing);
+}
@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use super::codes::TreeSpecies;
+use super::forest_property_data::{ForestPropertyData, StandFilter};
+use super::stand::Stand;
+use super::tree_stand_data::SnapshotSelector;
+
+/// Errors of loading a price table
+#[derive(Debug)]
+pub enum PriceTableError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    /// The file extension is not `json` or `toml`
+    UnknownFormat(String),
+}
+
+impl fmt::Display for PriceTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceTableError::Io(err) => write!(f, "I/O error: {}", err),
+            PriceTableError::Json(err) => write!(f, "Invalid JSON price table: {}", err),
+            PriceTableError::Toml(err) => write!(f, "Invalid TOML price table: {}", err),
+            PriceTableError::UnknownFormat(path) => write!(f, "Unknown price table format: {}", path),
+        }
+    }
+}
+
+impl std::error::Error for PriceTableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PriceTableError::Io(err) => Some(err),
+            PriceTableError::Json(err) => Some(err),
+            PriceTableError::Toml(err) => Some(err),
+            PriceTableError::UnknownFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for PriceTableError {
+    fn from(err: io::Error) -> Self {
+        PriceTableError::Io(err)
+    }
+}
+
+/// Stumpage prices of one species per m³
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct AssortmentPrices {
+    pub saw_log: f64,
+    pub pulp_wood: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SpeciesPrices {
+    pub tree_species: TreeSpecies,
+    #[serde(flatten)]
+    pub prices: AssortmentPrices,
+}
+
+/// Stem type codes of operation assortments that are saw logs and pulpwood
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StemTypes {
+    pub saw_log: Vec<u32>,
+    pub pulp_wood: Vec<u32>,
+}
+
+// Codes of the bundled data: 1 for saw logs and 5 for pulpwood
+impl Default for StemTypes {
+    fn default() -> Self {
+        StemTypes {
+            saw_log: vec![1],
+            pulp_wood: vec![5],
+        }
+    }
+}
+
+/// Stumpage prices per species and assortment. Species that are not listed get the default prices.
+///
+/// ```toml
+/// currency = "EUR"
+///
+/// [default]
+/// saw_log = 55.0
+/// pulp_wood = 18.0
+///
+/// [[species]]
+/// tree_species = 1
+/// saw_log = 62.0
+/// pulp_wood = 19.0
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    pub default: AssortmentPrices,
+    #[serde(default)]
+    pub species: Vec<SpeciesPrices>,
+    #[serde(default)]
+    pub stem_types: StemTypes,
+}
+
+fn default_currency() -> String {
+    "EUR".to_string()
+}
+
+impl PriceTable {
+    pub fn from_json_str(json: &str) -> Result<Self, PriceTableError> {
+        serde_json::from_str(json).map_err(PriceTableError::Json)
+    }
+
+    pub fn from_toml_str(toml: &str) -> Result<Self, PriceTableError> {
+        toml::from_str(toml).map_err(PriceTableError::Toml)
+    }
+
+    /// Reads a `.json` or `.toml` price table
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PriceTableError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => PriceTable::from_json_str(&content),
+            Some("toml") => PriceTable::from_toml_str(&content),
+            _ => Err(PriceTableError::UnknownFormat(path.display().to_string())),
+        }
+    }
+
+    pub fn prices(&self, species: TreeSpecies) -> AssortmentPrices {
+        self.species
+            .iter()
+            .find(|prices| prices.tree_species == species)
+            .map_or(self.default, |prices| prices.prices)
+    }
+
+    /// Price of an operation assortment, None for stem types that are not priced
+    pub fn assortment_price(&self, species: Option<TreeSpecies>, stem_type: u32) -> Option<f64> {
+        let prices = species.map_or(self.default, |species| self.prices(species));
+        if self.stem_types.saw_log.contains(&stem_type) {
+            Some(prices.saw_log)
+        } else if self.stem_types.pulp_wood.contains(&stem_type) {
+            Some(prices.pulp_wood)
+        } else {
+            None
+        }
+    }
+}
+
+/// Stumpage value of a stand's growing stock
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StandValue {
+    pub stand_id: String,
+    pub stand_number: String,
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+    /// Hectares
+    pub area: f64,
+    pub saw_log_value: f64,
+    pub pulp_wood_value: f64,
+    pub value: f64,
+    pub value_per_hectare: f64,
+}
+
+/// Stumpage value of a parcel or a real estate. Real estates have no parcel number.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct UnitValue {
+    pub real_estate_id: Option<u32>,
+    pub parcel_number: Option<i64>,
+    pub area: f64,
+    pub value: f64,
+}
+
+/// Value of a proposed cutting
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CuttingValue {
+    pub stand_id: String,
+    pub operation_id: u32,
+    pub proposal_year: u32,
+    /// m³
+    pub volume: f64,
+    pub value: f64,
+    /// Volume of assortments whose stem type has no price
+    pub unpriced_volume: f64,
+}
+
+/// Options of `ForestPropertyData::valuation_with_options`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValuationOptions {
+    pub filter: StandFilter,
+    pub snapshot: SnapshotSelector,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PropertyValuation {
+    pub currency: String,
+    pub value: f64,
+    pub stands: Vec<StandValue>,
+    pub parcels: Vec<UnitValue>,
+    pub real_estates: Vec<UnitValue>,
+    /// Proposed cuttings that have not been completed
+    pub cuttings: Vec<CuttingValue>,
+}
+
+impl PropertyValuation {
+    pub fn cutting_value(&self) -> f64 {
+        self.cuttings.iter().map(|cutting| cutting.value).sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+// Stumpage value of the selected strata. Stratum volumes are m³/ha.
+pub fn stand_value(stand: &Stand, prices: &PriceTable, snapshot: &SnapshotSelector) -> StandValue {
+    let basic_data = &stand.stand_basic_data;
+    let area = basic_data.area as f64;
+    let (mut saw_log_value, mut pulp_wood_value) = (0.0, 0.0);
+
+    if let Some(strata) = stand.get_strata_for(snapshot) {
+        for stratum in &strata.tree_stratum {
+            let species_prices = prices.prices(stratum.tree_species);
+            saw_log_value += stratum.saw_log_volume as f64 * area * species_prices.saw_log;
+            pulp_wood_value += stratum.pulp_wood_volume as f64 * area * species_prices.pulp_wood;
+        }
+    }
+    let value = saw_log_value + pulp_wood_value;
+
+    StandValue {
+        stand_id: stand.id.to_owned(),
+        stand_number: format!("{}{}", basic_data.stand_number, basic_data.stand_number_extension),
+        real_estate_id: stand.real_estate_id,
+        parcel_number: stand.parcel_number,
+        area,
+        saw_log_value,
+        pulp_wood_value,
+        value,
+        value_per_hectare: if area > 0.0 { value / area } else { 0.0 },
+    }
+}
+
+// Cuttings are valued by their assortments. A cutting without assortments is valued
+// at the average unit price of the stand's saw logs and pulpwood.
+fn cutting_values(stand: &Stand, prices: &PriceTable, snapshot: &SnapshotSelector, value: &StandValue) -> Vec<CuttingValue> {
+    let commercial_volume: f64 = stand.get_strata_for(snapshot)
+        .map(|strata| {
+            strata.tree_stratum.iter().map(|stratum| (stratum.saw_log_volume + stratum.pulp_wood_volume) as f64).sum::<f64>()
+        })
+        .unwrap_or(0.0) * value.area;
+    let unit_price = if commercial_volume > 0.0 { value.value / commercial_volume } else { 0.0 };
+
+    stand.operations()
+        .iter()
+        .filter(|operation| operation.is_cutting() && operation.completion_data.is_none())
+        .map(|operation| {
+            let mut cutting = CuttingValue {
+                stand_id: stand.id.to_owned(),
+                operation_id: operation.id,
+                proposal_year: operation.proposal_data.proposal_year,
+                volume: operation.cutting.as_ref().map_or(0.0, |cutting| cutting.cutting_volume as f64),
+                value: 0.0,
+                unpriced_volume: 0.0,
+            };
+
+            let assortments = operation.assortments();
+            if assortments.is_empty() {
+                cutting.value = cutting.volume * unit_price;
+            }
+            for assortment in assortments {
+                let volume = assortment.assortment_volume as f64;
+                match prices.assortment_price(assortment.tree_species, assortment.stem_type) {
+                    Some(price) => cutting.value += volume * price,
+                    None => cutting.unpriced_volume += volume,
+                }
+            }
+            cutting
+        })
+        .collect()
+}
+
+impl ForestPropertyData {
+    pub fn valuation(&self, prices: &PriceTable) -> PropertyValuation {
+        self.valuation_with_options(prices, &ValuationOptions::default())
+    }
+
+    pub fn valuation_with_options(&self, prices: &PriceTable, options: &ValuationOptions) -> PropertyValuation {
+        let mut valuation = PropertyValuation {
+            currency: prices.currency.to_owned(),
+            ..Default::default()
+        };
+        let mut parcels: BTreeMap<(Option<u32>, Option<i64>), UnitValue> = BTreeMap::new();
+        let mut real_estates: BTreeMap<Option<u32>, UnitValue> = BTreeMap::new();
+
+        for stand in self.get_stands_filtered(&options.filter) {
+            let value = stand_value(&stand, prices, &options.snapshot);
+            valuation.cuttings.extend(cutting_values(&stand, prices, &options.snapshot, &value));
+
+            // Stands without a real estate are only counted in the total
+            if stand.parcel_number.is_some() {
+                let parcel = parcels.entry((stand.real_estate_id, stand.parcel_number)).or_default();
+                parcel.area += value.area;
+                parcel.value += value.value;
+            }
+            if stand.real_estate_id.is_some() {
+                let real_estate = real_estates.entry(stand.real_estate_id).or_default();
+                real_estate.area += value.area;
+                real_estate.value += value.value;
+            }
+            valuation.value += value.value;
+            valuation.stands.push(value);
+        }
+
+        valuation.parcels = parcels
+            .into_iter()
+            .map(|((real_estate_id, parcel_number), value)| UnitValue { real_estate_id, parcel_number, ..value })
+            .collect();
+        valuation.real_estates = real_estates
+            .into_iter()
+            .map(|(real_estate_id, value)| UnitValue { real_estate_id, ..value })
+            .collect();
+
+        valuation
+    }
+}
+
+#[test]
+fn test_valuation() {
+    let toml = r#"
+        currency = "EUR"
+
+        [default]
+        saw_log = 50.0
+        pulp_wood = 20.0
+
+        [[species]]
+        tree_species = 1
+        saw_log = 60.0
+        pulp_wood = 20.0
+    "#;
+    let prices = PriceTable::from_toml_str(toml).unwrap();
+    let json = serde_json::to_string(&prices).unwrap();
+    assert_eq!(PriceTable::from_json_str(&json).unwrap(), prices);
+    assert_eq!(prices.prices(TreeSpecies::ScotsPine).saw_log, 60.0);
+    assert_eq!(prices.prices(TreeSpecies::NorwaySpruce).saw_log, 50.0);
+    assert_eq!(prices.assortment_price(Some(TreeSpecies::ScotsPine), 1), Some(60.0));
+    assert_eq!(prices.assortment_price(None, 11), None);
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let valuation = property.valuation(&prices);
+    let parcel_value: f64 = valuation.parcels.iter().map(|parcel| parcel.value).sum();
+    assert!(valuation.value > 0.0);
+    assert!((parcel_value - valuation.value).abs() < 1e-6 * valuation.value);
+    assert!(valuation.cutting_value() > 0.0);
+}
@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+use quick_xml::events::Event;
+use quick_xml::{DeError, Reader};
+use serde::de::DeserializeOwned;
+use crate::error::ForestDataError;
+use super::forest_property_data::*;
+use super::geometry::{Coordinates, DirectPositions, Exterior, Interior, LinearRing, MultiPolygon, Point, PointProperty, Polygon, PolygonGeometry, PolygonMember, PolygonProperty};
+use super::stand::{Stand, Stands};
+
+// Reads the encoding from the XML declaration, e.g. `<?xml version="1.0" encoding="iso-8859-1"?>`
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    let head = &bytes[..bytes.len().min(200)];
+    let head = String::from_utf8_lossy(head);
+    let declaration = &head[head.find("<?xml")?..];
+    let declaration = &declaration[..declaration.find("?>")?];
+    let start = declaration.find("encoding=")? + "encoding=".len();
+    let quote = declaration[start..].chars().next()?;
+    let value = &declaration[start + 1..];
+    let end = value.find(quote)?;
+
+    Some(value[..end].to_lowercase())
+}
+
+/// Decodes Forest Data XML bytes to a string using the encoding declared in the document.
+/// UTF-8 and ISO-8859-1 (Latin-1) are supported.
+pub fn decode_xml_bytes(bytes: &[u8]) -> Result<Cow<'_, str>, ForestDataError> {
+    let encoding = declared_encoding(bytes).unwrap_or_else(|| "utf-8".to_string());
+    decode_with_encoding(bytes, &encoding)
+}
+
+pub(crate) fn decode_with_encoding<'a>(bytes: &'a [u8], encoding: &str) -> Result<Cow<'a, str>, ForestDataError> {
+    match encoding {
+        "utf-8" | "utf8" => {
+            let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+            std::str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|e| ForestDataError::Encoding {
+                    encoding: encoding.to_string(),
+                    position: e.valid_up_to() as u64,
+                    message: e.to_string(),
+                })
+        }
+        "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" => {
+            if bytes.is_ascii() {
+                // ASCII is identical in both encodings, no need to copy
+                Ok(Cow::Borrowed(std::str::from_utf8(bytes).unwrap()))
+            } else {
+                Ok(Cow::Owned(bytes.iter().map(|&b| b as char).collect()))
+            }
+        }
+        _ => Err(ForestDataError::Encoding {
+            encoding: encoding.to_string(),
+            position: 0,
+            message: "Unsupported encoding".to_string(),
+        }),
+    }
+}
+
+/// Builds a schema error for a byte position in `xml`, computing the line and column.
+pub(crate) fn schema_error(xml: &str, position: usize, path: String, message: String) -> ForestDataError {
+    let position = position.min(xml.len());
+    let before = &xml.as_bytes()[..position];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = position - before.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1) + 1;
+
+    ForestDataError::Schema {
+        path,
+        position: position as u64,
+        line,
+        column,
+        message,
+    }
+}
+
+// Element with its byte span in the document
+struct ElementNode {
+    name: String,
+    start: usize,
+    end: usize,
+    children: Vec<ElementNode>,
+}
+
+impl ElementNode {
+    // Path segment of the child at `index`, with an XPath-style position if it has same-named siblings
+    fn child_segment(&self, index: usize) -> String {
+        let child = &self.children[index];
+        let same_named: Vec<usize> = self.children.iter().enumerate()
+            .filter(|(_, c)| c.name == child.name)
+            .map(|(i, _)| i)
+            .collect();
+
+        if same_named.len() > 1 {
+            let position = same_named.iter().position(|&i| i == index).unwrap() + 1;
+            format!("{}[{}]", child.name, position)
+        } else {
+            child.name.to_owned()
+        }
+    }
+}
+
+// Reads the element tree of the document. Fails with a schema error if the XML is not well-formed.
+fn read_element_tree(xml: &str) -> Result<ElementNode, ForestDataError> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<ElementNode> = Vec::new();
+    let mut root: Option<ElementNode> = None;
+
+    let path_of = |stack: &Vec<ElementNode>| stack.iter().map(|n| n.name.as_str()).collect::<Vec<_>>().join("/");
+
+    loop {
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => {
+                let position = reader.error_position() as usize;
+                return Err(schema_error(xml, position, path_of(&stack), e.to_string()));
+            }
+        };
+        let position = reader.buffer_position() as usize;
+
+        match event {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                stack.push(ElementNode { name, start: position - e.len() - 2, end: position, children: Vec::new() });
+            }
+            Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                let node = ElementNode { name, start: position - e.len() - 3, end: position, children: Vec::new() };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => root = Some(node),
+                }
+            }
+            Event::End(_) => {
+                let mut node = stack.pop().unwrap();
+                node.end = position;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => root = Some(node),
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match root {
+        Some(root) if stack.is_empty() => Ok(root),
+        _ => Err(schema_error(xml, xml.len(), path_of(&stack), "Unexpected end of document".to_string())),
+    }
+}
+
+fn check<T: DeserializeOwned>(fragment: &str) -> Option<Result<(), DeError>> {
+    Some(quick_xml::de::from_str::<T>(fragment).map(|_| ()))
+}
+
+// Deserializes an element fragment into the model type of the element, if the element has one
+fn probe_element(name: &str, fragment: &str) -> Option<Result<(), DeError>> {
+    match name {
+        "RealEstates" => check::<RealEstates>(fragment),
+        "RealEstate" => check::<RealEstate>(fragment),
+        "Parcels" => check::<Parcels>(fragment),
+        "Parcel" => check::<Parcel>(fragment),
+        "Stands" => check::<Stands>(fragment),
+        "Stand" => check::<Stand>(fragment),
+        "StandBasicData" => check::<StandBasicData>(fragment),
+        "Identifiers" => check::<Identifiers>(fragment),
+        "Identifier" => check::<Identifier>(fragment),
+        "PolygonGeometry" => check::<PolygonGeometry>(fragment),
+        "pointProperty" => check::<PointProperty>(fragment),
+        "Point" => check::<Point>(fragment),
+        "polygonProperty" | "multiPolygonProperty" | "multiSurfaceProperty" => check::<PolygonProperty>(fragment),
+        "MultiPolygon" | "MultiSurface" => check::<MultiPolygon>(fragment),
+        "polygonMember" | "surfaceMember" => check::<PolygonMember>(fragment),
+        "Polygon" => check::<Polygon>(fragment),
+        "exterior" | "outerBoundaryIs" => check::<Exterior>(fragment),
+        "interior" | "innerBoundaryIs" => check::<Interior>(fragment),
+        "LinearRing" => check::<LinearRing>(fragment),
+        "coordinates" => check::<Coordinates>(fragment),
+        "pos" | "posList" => check::<DirectPositions>(fragment),
+        "SpecialFeatures" => check::<SpecialFeatures>(fragment),
+        "SpecialFeature" => check::<SpecialFeature>(fragment),
+        "Operations" => check::<Operations>(fragment),
+        "Operation" => check::<Operation>(fragment),
+        "CompletionData" => check::<CompletionData>(fragment),
+        "Specifications" => check::<Specifications>(fragment),
+        "Specification" => check::<Specification>(fragment),
+        "ProposalData" => check::<ProposalData>(fragment),
+        "Cutting" => check::<Cutting>(fragment),
+        "Assortments" => check::<Assortments>(fragment),
+        "Assortment" => check::<Assortment>(fragment),
+        "TreeStandData" => check::<TreeStandData>(fragment),
+        "TreeStandDataDate" => check::<TreeStandDataDate>(fragment),
+        "DeadTreeStrata" => check::<DeadTreeStrata>(fragment),
+        "DeadTreeStratum" => check::<DeadTreeStratum>(fragment),
+        "TreeStrata" => check::<TreeStrata>(fragment),
+        "TreeStratum" => check::<TreeStratum>(fragment),
+        "TreeStandSummary" => check::<TreeStandSummary>(fragment),
+        _ => None,
+    }
+}
+
+// Element that failed to deserialize, relative to the root of the parsed document
+pub(crate) struct FailingElement {
+    pub root: String,
+    // Path segments below the root, empty if the root itself failed
+    pub segments: Vec<String>,
+    pub position: usize,
+    pub message: String,
+}
+
+impl FailingElement {
+    fn path(&self) -> String {
+        if self.segments.is_empty() {
+            self.root.to_owned()
+        } else {
+            self.segments.join("/")
+        }
+    }
+}
+
+// Finds the deepest element that fails to deserialize into its model type
+pub(crate) fn find_failing_element(xml: &str, error: DeError) -> Result<FailingElement, ForestDataError> {
+    let root = read_element_tree(xml)?;
+
+    let mut node = &root;
+    let mut segments: Vec<String> = Vec::new();
+    let mut message = error.to_string();
+
+    'descend: loop {
+        for (i, child) in node.children.iter().enumerate() {
+            if let Some(Err(e)) = probe_element(&child.name, &xml[child.start..child.end]) {
+                segments.push(node.child_segment(i));
+                message = e.to_string();
+                node = child;
+                continue 'descend;
+            }
+        }
+        break;
+    }
+
+    Ok(FailingElement {
+        root: root.name.to_owned(),
+        segments,
+        position: node.start,
+        message,
+    })
+}
+
+/// Finds the deepest element of `xml` that fails to deserialize into its model type and
+/// reports it as a schema error. `error` is the error of the whole document and is used
+/// when no single element can be blamed.
+pub(crate) fn locate_schema_error(xml: &str, error: DeError) -> ForestDataError {
+    match find_failing_element(xml, error) {
+        Ok(failing) => schema_error(xml, failing.position, failing.path(), failing.message),
+        Err(syntax_error) => syntax_error,
+    }
+}
+
+#[test]
+fn test_locate_schema_error() {
+    let xml = std::fs::read_to_string("forestpropertydata.xml").unwrap();
+    let xml = xml.replacen("<st:Area>5.9145</st:Area>", "<st:Area>not a number</st:Area>", 1);
+
+    let error = ForestPropertyData::try_parse_from_str(&xml).unwrap_err();
+
+    assert!(error.is_schema());
+    assert_eq!(error.path(), Some("RealEstates/RealEstate/Parcels/Parcel[1]/Stands/Stand[1]/StandBasicData"));
+    match error {
+        ForestDataError::Schema { line, column, .. } => assert_eq!((line, column), (16, 15)),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_malformed_xml_and_encoding_errors() {
+    let error = ForestPropertyData::try_parse_from_str("<ForestPropertyData><RealEstates></ForestPropertyData>").unwrap_err();
+    assert!(error.is_schema());
+
+    let error = decode_xml_bytes(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><a>\xFF</a>").unwrap_err();
+    assert!(error.is_encoding());
+    assert_eq!(error.position(), Some(41));
+
+    let decoded = decode_xml_bytes(b"<?xml version=\"1.0\" encoding=\"iso-8859-1\"?><a>\xC4</a>").unwrap();
+    assert!(decoded.ends_with("<a>\u{C4}</a>"));
+}
+
+#[test]
+fn test_parse_bundled_file() {
+    let property = ForestPropertyData::try_from_xml_file("forestpropertydata.xml");
+    assert!(property.is_ok(), "{:?}", property.err());
+
+    let missing = ForestPropertyData::try_from_xml_file("does_not_exist.xml").unwrap_err();
+    assert!(missing.is_io());
+}
@@ -0,0 +1,532 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use crate::error::ForestDataError;
+use super::codes::ChangeState;
+use super::forest_property_data::*;
+use super::geometry::{Coordinates, DirectPositions, LinearRing, Polygon, PolygonGeometry, PolygonProperty};
+use super::schema_version::SchemaVersion;
+use super::stand::Stand;
+
+// Namespace declarations of the root element, as written by Tapio ForestKIT
+const NAMESPACES: [(&str, &str); 15] = [
+    ("xmlns", "http://standardit.tapio.fi/schemas/forestData"),
+    ("xmlns:re", "http://standardit.tapio.fi/schemas/forestData/realEstate"),
+    ("xmlns:st", "http://standardit.tapio.fi/schemas/forestData/stand/2010/08/31"),
+    ("xmlns:ts", "http://standardit.tapio.fi/schemas/forestData/treeStand"),
+    ("xmlns:tst", "http://standardit.tapio.fi/schemas/forestData/treeStratum"),
+    ("xmlns:dts", "http://standardit.tapio.fi/schemas/forestData/deadTreeStrata"),
+    ("xmlns:tss", "http://standardit.tapio.fi/schemas/forestData/treeStandSummary"),
+    ("xmlns:op", "http://standardit.tapio.fi/schemas/forestData/operation"),
+    ("xmlns:sf", "http://standardit.tapio.fi/schemas/forestData/specialFeature/2010/08/31"),
+    ("xmlns:gdt", "http://standardit.tapio.fi/schemas/forestData/common/geometricDataTypes"),
+    ("xmlns:co", "http://standardit.tapio.fi/schemas/forestData/common"),
+    ("xmlns:gml", "http://www.opengis.net/gml"),
+    ("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"),
+    ("xmlns:xlink", "http://www.w3.org/1999/xlink"),
+    ("xsi:schemaLocation", "http://standardit.tapio.fi/schemas/forestData ForestData.xsd"),
+];
+
+fn write_error(err: quick_xml::Error) -> ForestDataError {
+    ForestDataError::Io(io::Error::other(err))
+}
+
+/// Writes `ForestPropertyData` as a Forest Data XML document. Elements are written
+/// in the order of the Forest Data schema with the same namespace prefixes as in Tapio exports.
+pub struct ForestDataWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl<W: Write> ForestDataWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ForestDataWriter {
+            writer: Writer::new_with_indent(inner, b' ', 2),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+
+    fn start(&mut self, name: &str, attributes: &[(&str, &str)]) -> Result<(), ForestDataError> {
+        let mut element = BytesStart::new(name);
+        for &(key, value) in attributes {
+            element.push_attribute((key, value));
+        }
+        self.writer.write_event(Event::Start(element)).map_err(write_error)
+    }
+
+    // Start of a GML geometry with its srsName and srsDimension attributes, if set
+    fn start_geometry(&mut self, name: &str, srs_name: &str, srs_dimension: Option<usize>) -> Result<(), ForestDataError> {
+        let dimension = srs_dimension.map(|dimension| dimension.to_string());
+        let mut attributes = Vec::new();
+        if !srs_name.is_empty() {
+            attributes.push(("srsName", srs_name));
+        }
+        if let Some(dimension) = &dimension {
+            attributes.push(("srsDimension", dimension.as_str()));
+        }
+        self.start(name, &attributes)
+    }
+
+    fn end(&mut self, name: &str) -> Result<(), ForestDataError> {
+        self.writer.write_event(Event::End(BytesEnd::new(name))).map_err(write_error)
+    }
+
+    fn empty(&mut self, name: &str) -> Result<(), ForestDataError> {
+        self.writer.write_event(Event::Empty(BytesStart::new(name))).map_err(write_error)
+    }
+
+    fn leaf<T: Display>(&mut self, name: &str, value: T) -> Result<(), ForestDataError> {
+        let text = value.to_string();
+        if text.is_empty() {
+            return self.empty(name);
+        }
+        self.start(name, &[])?;
+        self.writer.write_event(Event::Text(BytesText::new(&text))).map_err(write_error)?;
+        self.end(name)
+    }
+
+    fn text_element(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) -> Result<(), ForestDataError> {
+        self.start(name, attributes)?;
+        self.writer.write_event(Event::Text(BytesText::new(text))).map_err(write_error)?;
+        self.end(name)
+    }
+
+    fn optional_leaf<T: Display>(&mut self, name: &str, value: &Option<T>) -> Result<(), ForestDataError> {
+        match value {
+            Some(value) => self.leaf(name, value),
+            None => Ok(()),
+        }
+    }
+
+    fn change_state(&mut self, change_state: &Option<ChangeState>) -> Result<(), ForestDataError> {
+        self.optional_leaf("co:ChangeState", &change_state.map(|state| state.code()))
+    }
+
+    // Fields that default to zero when missing are only written if they have a value
+    fn nonzero_leaf(&mut self, name: &str, value: f32) -> Result<(), ForestDataError> {
+        if value != 0.0 {
+            self.leaf(name, value)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_property(&mut self, property: &ForestPropertyData) -> Result<(), ForestDataError> {
+        if !property.schema_version.is_supported() {
+            return Err(ForestDataError::UnsupportedVersion(property.schema_version));
+        }
+
+        self.writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .map_err(write_error)?;
+
+        // Elements are written in the MV1.7 layout with the dated stand namespace, so documents
+        // of older or unknown revisions are declared as MV1.7
+        let version = match property.schema_version {
+            SchemaVersion::Legacy | SchemaVersion::Unknown => SchemaVersion::Mv1_7,
+            version => version,
+        };
+        let comment = format!("SchemaVersio: {}", version);
+        self.writer
+            .write_event(Event::Comment(BytesText::from_escaped(comment)))
+            .map_err(write_error)?;
+
+        self.start("ForestPropertyData", &NAMESPACES)?;
+        if !property.real_estates.real_estate.is_empty() || property.stands.is_none() {
+            self.start("re:RealEstates", &[])?;
+            for real_estate in &property.real_estates.real_estate {
+                self.write_real_estate(real_estate)?;
+            }
+            self.end("re:RealEstates")?;
+        }
+        if let Some(stands) = &property.stands {
+            self.start("st:Stands", &[])?;
+            for stand in &stands.stand {
+                self.write_stand(stand)?;
+            }
+            self.end("st:Stands")?;
+        }
+        self.end("ForestPropertyData")
+    }
+
+    fn write_real_estate(&mut self, real_estate: &RealEstate) -> Result<(), ForestDataError> {
+        self.start("re:RealEstate", &[("id", &real_estate.id.to_string())])?;
+        self.leaf("re:MunicipalityNumber", real_estate.municipality_number)?;
+        self.leaf("re:AreaNumber", real_estate.area_number)?;
+        self.leaf("re:GroupNumber", real_estate.group_number)?;
+        self.leaf("re:UnitNumber", real_estate.unit_number)?;
+        self.leaf("re:RealEstateName", &real_estate.real_estate_name)?;
+
+        self.start("re:Parcels", &[])?;
+        for parcel in &real_estate.parcels.parcel {
+            self.start("re:Parcel", &[("id", &parcel.id.to_string())])?;
+            self.leaf("re:ParcelNumber", parcel.parcel_number)?;
+            self.start("st:Stands", &[])?;
+            for stand in &parcel.stands.stand {
+                self.write_stand(stand)?;
+            }
+            self.end("st:Stands")?;
+            self.end("re:Parcel")?;
+        }
+        self.end("re:Parcels")?;
+
+        self.end("re:RealEstate")
+    }
+
+    pub fn write_stand(&mut self, stand: &Stand) -> Result<(), ForestDataError> {
+        self.start("st:Stand", &[("id", &stand.id)])?;
+        self.write_stand_basic_data(&stand.stand_basic_data)?;
+
+        if let Some(tree_stand_data) = &stand.tree_stand_data {
+            self.write_tree_stand_data(tree_stand_data)?;
+        }
+
+        if let Some(operations) = &stand.operations {
+            self.start("op:Operations", &[])?;
+            for operation in &operations.operation {
+                self.write_operation(operation)?;
+            }
+            self.end("op:Operations")?;
+        }
+
+        if let Some(special_features) = &stand.special_features {
+            self.start("st:SpecialFeatures", &[])?;
+            for feature in &special_features.special_feature {
+                self.start("st:SpecialFeature", &[("id", &feature.id.to_string())])?;
+                self.change_state(&feature.change_state)?;
+                self.leaf("sf:FeatureCode", &feature.feature_code)?;
+                self.optional_leaf("sf:FeatureAdditionalCode", &feature.feature_additional_code)?;
+                self.end("st:SpecialFeature")?;
+            }
+            self.end("st:SpecialFeatures")?;
+        }
+
+        self.end("st:Stand")
+    }
+
+    fn write_stand_basic_data(&mut self, data: &StandBasicData) -> Result<(), ForestDataError> {
+        self.start("st:StandBasicData", &[])?;
+        self.change_state(&data.change_state)?;
+        self.leaf("co:ChangeTime", &data.change_time)?;
+        self.leaf("st:CompleteState", data.complete_state)?;
+
+        if let Some(identifiers) = &data.identifiers {
+            self.start("st:Identifiers", &[])?;
+            self.start("st:Identifier", &[])?;
+            self.leaf("co:IdentifierType", &identifiers.identifier.identifier_type)?;
+            self.leaf("co:IdentifierValue", &identifiers.identifier.identifier_value)?;
+            self.end("st:Identifier")?;
+            self.end("st:Identifiers")?;
+        }
+
+        self.leaf("st:StandNumber", data.stand_number)?;
+        self.leaf("st:StandNumberExtension", &data.stand_number_extension)?;
+        self.leaf("st:MainGroup", data.main_group.code())?;
+        self.optional_leaf("st:SubGroup", &data.sub_group)?;
+        self.optional_leaf("st:FertilityClass", &data.fertility_class.map(|code| code.code()))?;
+        self.optional_leaf("st:SoilType", &data.soil_type.map(|code| code.code()))?;
+        self.optional_leaf("st:DrainageState", &data.drainage_state.map(|code| code.code()))?;
+        self.optional_leaf("st:DitchingYear", &data.ditching_year)?;
+        self.optional_leaf("st:DevelopmentClass", &data.development_class.as_ref().map(|code| code.code()))?;
+        self.optional_leaf("st:StandQuality", &data.stand_quality)?;
+        self.optional_leaf("st:MainTreeSpecies", &data.main_tree_species.map(|code| code.code()))?;
+        self.optional_leaf("st:Accessibility", &data.accessibility)?;
+        if data.cutting_restriction != 0 {
+            self.leaf("st:CuttingRestriction", data.cutting_restriction)?;
+        }
+        self.leaf("st:StandBasicDataDate", &data.stand_basic_data_date)?;
+        self.optional_leaf("st:StandInfo", &data.stand_info)?;
+        self.leaf("st:Area", data.area)?;
+        self.optional_leaf("st:AreaDecrease", &data.area_decrease)?;
+        self.write_polygon_geometry(&data.polygon_geometry)?;
+
+        self.end("st:StandBasicData")
+    }
+
+    fn write_polygon_geometry(&mut self, geometry: &PolygonGeometry) -> Result<(), ForestDataError> {
+        self.start("gdt:PolygonGeometry", &[])?;
+
+        let point = &geometry.point_property.point;
+        self.start("gml:pointProperty", &[])?;
+        self.start_geometry("gml:Point", &point.srs_name, point.srs_dimension)?;
+        if let Some(coordinates) = &point.coordinates {
+            self.write_coordinates(coordinates)?;
+        }
+        if let Some(pos) = &point.pos {
+            self.write_positions("gml:pos", pos)?;
+        }
+        self.end("gml:Point")?;
+        self.end("gml:pointProperty")?;
+
+        let property = &geometry.polygon_property;
+        if property.polygon.is_some() || property.multi_polygon.is_some() {
+            self.write_polygon_property("gml:polygonProperty", property)?;
+        }
+        if let Some(property) = &geometry.multi_polygon_property {
+            self.write_polygon_property("gml:multiPolygonProperty", property)?;
+        }
+
+        self.end("gdt:PolygonGeometry")
+    }
+
+    fn write_polygon_property(&mut self, name: &str, property: &PolygonProperty) -> Result<(), ForestDataError> {
+        self.start(name, &[])?;
+        if let Some(polygon) = &property.polygon {
+            self.write_polygon(polygon)?;
+        }
+        if let Some(multi_polygon) = &property.multi_polygon {
+            self.start_geometry("gml:MultiPolygon", &multi_polygon.srs_name, multi_polygon.srs_dimension)?;
+            for member in &multi_polygon.polygon_member {
+                self.start("gml:polygonMember", &[])?;
+                self.write_polygon(&member.polygon)?;
+                self.end("gml:polygonMember")?;
+            }
+            self.end("gml:MultiPolygon")?;
+        }
+        self.end(name)
+    }
+
+    fn write_polygon(&mut self, polygon: &Polygon) -> Result<(), ForestDataError> {
+        self.start_geometry("gml:Polygon", &polygon.srs_name, polygon.srs_dimension)?;
+        self.start("gml:exterior", &[])?;
+        self.write_linear_ring(&polygon.exterior.linear_ring)?;
+        self.end("gml:exterior")?;
+        for interior in &polygon.interior {
+            self.start("gml:interior", &[])?;
+            self.write_linear_ring(&interior.linear_ring)?;
+            self.end("gml:interior")?;
+        }
+        self.end("gml:Polygon")
+    }
+
+    fn write_linear_ring(&mut self, ring: &LinearRing) -> Result<(), ForestDataError> {
+        self.start("gml:LinearRing", &[])?;
+        if let Some(coordinates) = &ring.coordinates {
+            self.write_coordinates(coordinates)?;
+        }
+        if let Some(pos_list) = &ring.pos_list {
+            self.write_positions("gml:posList", pos_list)?;
+        }
+        for pos in &ring.pos {
+            self.write_positions("gml:pos", pos)?;
+        }
+        self.end("gml:LinearRing")
+    }
+
+    fn write_coordinates(&mut self, coordinates: &Coordinates) -> Result<(), ForestDataError> {
+        let separators = [("decimal", &coordinates.decimal), ("cs", &coordinates.cs), ("ts", &coordinates.ts)];
+        let attributes: Vec<(&str, &str)> = separators
+            .iter()
+            .filter_map(|(key, value)| value.as_deref().map(|value| (*key, value)))
+            .collect();
+
+        self.text_element("gml:coordinates", &attributes, &coordinates.value)
+    }
+
+    fn write_positions(&mut self, name: &str, positions: &DirectPositions) -> Result<(), ForestDataError> {
+        match positions.srs_dimension {
+            Some(dimension) => self.text_element(name, &[("srsDimension", &dimension.to_string())], &positions.value),
+            None => self.text_element(name, &[], &positions.value),
+        }
+    }
+
+    fn write_tree_stand_data(&mut self, data: &TreeStandData) -> Result<(), ForestDataError> {
+        self.start("ts:TreeStandData", &[])?;
+
+        for data_date in &data.tree_stand_data_date {
+            let data_type = data_date.tree_stand_data_date_type.code().to_string();
+            self.start("ts:TreeStandDataDate", &[("date", data_date.date.as_str()), ("type", data_type.as_str())])?;
+
+            self.start("tst:TreeStrata", &[])?;
+            for stratum in &data_date.tree_strata.tree_stratum {
+                self.write_tree_stratum(stratum)?;
+            }
+            self.end("tst:TreeStrata")?;
+
+            if let Some(dead_tree_strata) = &data_date.dead_tree_strata {
+                self.start("dts:DeadTreeStrata", &[])?;
+                for stratum in &dead_tree_strata.dead_tree_stratum {
+                    self.start("dts:DeadTreeStratum", &[("id", &stratum.id.to_string())])?;
+                    self.change_state(&stratum.change_state)?;
+                    self.leaf("dts:DeadTreeType", stratum.dead_tree_type.code())?;
+                    self.leaf("dts:TreeSpecies", stratum.tree_species.code())?;
+                    self.nonzero_leaf("dts:MeanDiameter", stratum.mean_diameter)?;
+                    self.nonzero_leaf("dts:Volume", stratum.volume)?;
+                    self.end("dts:DeadTreeStratum")?;
+                }
+                self.end("dts:DeadTreeStrata")?;
+            }
+
+            if let Some(summary) = &data_date.tree_stand_summary {
+                self.write_tree_stand_summary(summary)?;
+            }
+
+            self.end("ts:TreeStandDataDate")?;
+        }
+
+        self.end("ts:TreeStandData")
+    }
+
+    fn write_tree_stratum(&mut self, stratum: &TreeStratum) -> Result<(), ForestDataError> {
+        self.start("tst:TreeStratum", &[("id", &stratum.id.to_string())])?;
+        self.change_state(&stratum.change_state)?;
+        self.leaf("tst:StratumNumber", stratum.stratum_number)?;
+        self.leaf("tst:TreeSpecies", stratum.tree_species.code())?;
+        self.leaf("tst:Storey", stratum.storey.code())?;
+        self.leaf("tst:Age", stratum.age)?;
+        self.nonzero_leaf("tst:BasalArea", stratum.basal_area)?;
+        if stratum.stem_count != 0 {
+            self.leaf("tst:StemCount", stratum.stem_count)?;
+        }
+        self.nonzero_leaf("tst:MeanDiameter", stratum.mean_diameter)?;
+        self.leaf("tst:MeanHeight", stratum.mean_height)?;
+        self.nonzero_leaf("tst:Volume", stratum.volume)?;
+        self.nonzero_leaf("tst:SawLogPercent", stratum.saw_log_percent)?;
+        self.nonzero_leaf("tst:SawLogVolume", stratum.saw_log_volume)?;
+        self.nonzero_leaf("tst:PulpWoodVolume", stratum.pulp_wood_volume)?;
+        self.nonzero_leaf("tst:VolumeGrowth", stratum.volume_growth)?;
+        self.leaf("co:DataSource", stratum.data_source)?;
+        self.end("tst:TreeStratum")
+    }
+
+    fn write_tree_stand_summary(&mut self, summary: &TreeStandSummary) -> Result<(), ForestDataError> {
+        self.start("tss:TreeStandSummary", &[("id", &summary.id.to_string())])?;
+        self.change_state(&summary.change_state)?;
+        self.leaf("tss:MeanAge", summary.mean_age)?;
+        self.leaf("tss:BasalArea", summary.basal_area)?;
+        self.leaf("tss:StemCount", summary.stem_count)?;
+        self.leaf("tss:MeanDiameter", summary.mean_diameter)?;
+        self.leaf("tss:MeanHeight", summary.mean_height)?;
+        self.leaf("tss:Volume", summary.volume)?;
+        self.nonzero_leaf("tss:SawLogVolume", summary.saw_log_volume)?;
+        self.nonzero_leaf("tss:PulpWoodVolume", summary.pulp_wood_volume)?;
+        self.leaf("tss:VolumeGrowth", summary.volume_growth)?;
+        self.optional_leaf("tss:Value", &summary.value)?;
+        self.nonzero_leaf("tss:ValueGrowthPercent", summary.value_growth_percent)?;
+        self.end("tss:TreeStandSummary")
+    }
+
+    fn write_operation(&mut self, operation: &Operation) -> Result<(), ForestDataError> {
+        let id = operation.id.to_string();
+        self.start("op:Operation", &[("mainType", operation.main_type.as_str()), ("id", id.as_str())])?;
+        self.change_state(&operation.change_state)?;
+        self.leaf("co:ChangeTime", &operation.change_time)?;
+        self.leaf("op:OperationType", operation.operation_type.code())?;
+
+        self.start("op:ProposalData", &[])?;
+        self.leaf("op:ProposalType", operation.proposal_data.proposal_type)?;
+        self.leaf("op:ProposalYear", operation.proposal_data.proposal_year)?;
+        self.end("op:ProposalData")?;
+
+        self.optional_leaf("op:OperationInfo", &operation.operation_info)?;
+
+        if let Some(completion_data) = &operation.completion_data {
+            self.start("op:CompletionData", &[])?;
+            self.leaf("op:CompletionDate", &completion_data.completion_date)?;
+            self.end("op:CompletionData")?;
+        }
+
+        if let Some(specifications) = &operation.specifications {
+            self.start("op:Specifications", &[])?;
+            for specification in &specifications.specification {
+                self.start("op:Specification", &[("id", &specification.id.to_string())])?;
+                self.change_state(&specification.change_state)?;
+                self.leaf("op:SpecificationCode", specification.specification_code)?;
+                self.end("op:Specification")?;
+            }
+            self.end("op:Specifications")?;
+        }
+
+        if operation.silviculture.is_some() {
+            self.empty("op:Silviculture")?;
+        }
+
+        if let Some(cutting) = &operation.cutting {
+            self.start("op:Cutting", &[])?;
+            self.nonzero_leaf("op:CuttingVolume", cutting.cutting_volume)?;
+            if let Some(assortments) = &cutting.assortments {
+                self.start("op:Assortments", &[])?;
+                for assortment in &assortments.assortment {
+                    self.start("op:Assortment", &[("id", &assortment.id.to_string())])?;
+                    self.change_state(&assortment.change_state)?;
+                    self.optional_leaf("op:TreeSpecies", &assortment.tree_species.map(|code| code.code()))?;
+                    if assortment.stem_type != 0 {
+                        self.leaf("op:StemType", assortment.stem_type)?;
+                    }
+                    self.nonzero_leaf("op:AssortmentVolume", assortment.assortment_volume)?;
+                    self.end("op:Assortment")?;
+                }
+                self.end("op:Assortments")?;
+            }
+            self.end("op:Cutting")?;
+        }
+
+        self.end("op:Operation")
+    }
+}
+
+impl ForestPropertyData {
+    pub fn write_xml<W: Write>(&self, writer: W) -> Result<W, ForestDataError> {
+        let mut writer = ForestDataWriter::new(writer);
+        writer.write_property(self)?;
+        Ok(writer.into_inner())
+    }
+
+    pub fn to_xml_string(&self) -> Result<String, ForestDataError> {
+        let bytes = self.write_xml(Vec::new())?;
+        Ok(String::from_utf8(bytes).expect("Writer only produces UTF-8"))
+    }
+
+    pub fn write_to_xml_file(&self, path: &str) -> Result<(), ForestDataError> {
+        let file = File::create(path)?;
+        let mut writer = self.write_xml(BufWriter::new(file))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_xml_round_trip() {
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+
+    let xml = property.to_xml_string().unwrap();
+    let round_tripped = ForestPropertyData::try_from_xml_str(&xml).unwrap();
+
+    assert!(xml.contains("<st:Stand id=\"2553941\">"));
+    assert!(xml.contains("xmlns:gml=\"http://www.opengis.net/gml\""));
+    assert_eq!(property, round_tripped);
+}
+
+#[test]
+fn test_xml_writes_all_elements() {
+    use std::collections::BTreeMap;
+
+    // Number of start tags per element name
+    fn element_counts(xml: &str) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for tag in xml.split('<').skip(1) {
+            if tag.starts_with(['/', '?', '!']) {
+                continue;
+            }
+            let name = tag.split(|c: char| c.is_whitespace() || c == '>' || c == '/').next().unwrap();
+            *counts.entry(name.to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    let source = String::from_utf8_lossy(&std::fs::read("forestpropertydata.xml").unwrap()).into_owned();
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let xml = property.to_xml_string().unwrap();
+
+    let source_counts = element_counts(&source);
+    let written_counts = element_counts(&xml);
+    // Numeric fields that default to zero may be written where the source left them out
+    assert!(source_counts.keys().eq(written_counts.keys()));
+    for name in ["co:ChangeState", "co:ChangeTime", "tss:Value", "st:SpecialFeature", "op:Assortment", "tst:TreeStratum"] {
+        assert_eq!(written_counts[name], source_counts[name], "{}", name);
+    }
+    assert!(xml.contains(&format!("<!--SchemaVersio: {}-->", property.schema_version)));
+}
@@ -1,10 +1,10 @@
-use crate::{forest_property::{compartment::{Compartment, CompartmentArea}, tree::Tree}, geometry_utils::get_min_max_coordinates};
+use crate::{forest_property::{biomass::tree_biomass, codes::Language, compartment::{Compartment, CompartmentArea}, dead_wood::{DeadTree, DeadWoodGeometry}, tree::Tree}, geometry_utils::get_min_max_coordinates};
 
 use geo::Polygon;
 use geojson::{Feature, FeatureCollection, GeoJson, Geometry as GeoJsonGeometry, Value};
 
 // Function to convert a Polygon into a GeoJSON Feature
-fn convert_polygon_to_feature(polygon: &Polygon<f64>) -> Feature {
+pub(crate) fn convert_polygon_to_feature(polygon: &Polygon<f64>, properties: Option<serde_json::Map<String, serde_json::Value>>) -> Feature {
     let exterior_coords: Vec<Vec<f64>> = polygon.exterior().points()
         .map(|point| vec![point.x(), point.y()])
         .collect();
@@ -17,13 +17,22 @@ fn convert_polygon_to_feature(polygon: &Polygon<f64>) -> Feature {
 
     Feature {
         geometry: Some(geometry),
-        properties: None,
+        properties,
         id: None,
         bbox: None,
         foreign_members: None,
     }
 }
 
+// Properties identifying the stand, real estate and parcel of a compartment
+pub(crate) fn compartment_properties(stand_number: &str, real_estate_id: Option<u32>, parcel_number: Option<i64>) -> serde_json::Map<String, serde_json::Value> {
+    let mut properties = serde_json::Map::new();
+    properties.insert("stand_number".to_string(), serde_json::json!(stand_number));
+    properties.insert("real_estate_id".to_string(), serde_json::json!(real_estate_id));
+    properties.insert("parcel_number".to_string(), serde_json::json!(parcel_number));
+    properties
+}
+
 // Function to convert a Tree into a GeoJSON Feature
 fn convert_tree_to_feature(tree: &Tree) -> Feature {
     let point = vec![tree.position().0, tree.position().1];
@@ -35,6 +44,14 @@ fn convert_tree_to_feature(tree: &Tree) -> Feature {
 
     let mut properties = serde_json::Map::new();
     properties.insert("species".to_string(), serde_json::json!(tree.species()));
+    properties.insert("species_name".to_string(), serde_json::json!(tree.species().name(Language::English)));
+    properties.insert("diameter".to_string(), serde_json::json!(tree.diameter()));
+    properties.insert("height".to_string(), serde_json::json!(tree.height()));
+    properties.insert("carbon".to_string(), serde_json::json!(tree_biomass(tree).carbon()));
+    let crown = tree.crown();
+    properties.insert("crown_radius".to_string(), serde_json::json!(crown.radius));
+    properties.insert("crown_base_height".to_string(), serde_json::json!(crown.base_height));
+    properties.insert("crown_shape".to_string(), serde_json::json!(crown.shape));
 
     Feature {
         geometry: Some(point_geometry),
@@ -45,6 +62,55 @@ fn convert_tree_to_feature(tree: &Tree) -> Feature {
     }
 }
 
+// Snags as points and fallen logs as lines from the root end to the top
+fn convert_dead_tree_to_feature(dead_tree: &DeadTree, stand_number: &str) -> Feature {
+    let value = match dead_tree.geometry {
+        DeadWoodGeometry::Standing(point) => Value::Point(vec![point.x(), point.y()]),
+        DeadWoodGeometry::Fallen(line) => Value::LineString(vec![vec![line.start.x, line.start.y], vec![line.end.x, line.end.y]]),
+    };
+
+    let mut properties = serde_json::Map::new();
+    properties.insert("stand_number".to_string(), serde_json::json!(stand_number));
+    properties.insert("dead_tree_type".to_string(), serde_json::json!(dead_tree.dead_tree_type));
+    properties.insert("species".to_string(), serde_json::json!(dead_tree.species));
+    properties.insert("diameter".to_string(), serde_json::json!(dead_tree.diameter));
+    properties.insert("length".to_string(), serde_json::json!(dead_tree.length));
+    properties.insert("volume".to_string(), serde_json::json!(dead_tree.volume));
+
+    Feature {
+        geometry: Some(GeoJsonGeometry::new(value)),
+        properties: Some(properties),
+        id: None,
+        bbox: None,
+        foreign_members: None,
+    }
+}
+
+fn dead_tree_features(dead_trees: &[DeadTree], stand_number: &str) -> Vec<Feature> {
+    dead_trees.iter().map(|dead_tree| convert_dead_tree_to_feature(dead_tree, stand_number)).collect()
+}
+
+// Dead wood of the compartments as a layer of its own
+pub fn dead_wood_to_geojson(compartments: &[Compartment]) -> GeoJson {
+    let features = compartments
+        .iter()
+        .flat_map(|compartment| dead_tree_features(&compartment.dead_trees, &compartment.stand_number))
+        .collect();
+
+    GeoJson::FeatureCollection(FeatureCollection {
+        features,
+        bbox: None,
+        foreign_members: None,
+    })
+}
+
+// Adds the features of a feature collection `layer` to `geojson`
+pub fn add_layer(geojson: &mut GeoJson, layer: GeoJson) {
+    if let (GeoJson::FeatureCollection(collection), GeoJson::FeatureCollection(layer)) = (geojson, layer) {
+        collection.features.extend(layer.features);
+    }
+}
+
 pub fn all_compartments_to_geojson(
         compartments: Vec<Compartment>,
         buildings: &GeoJson, 
@@ -58,12 +124,16 @@ pub fn all_compartments_to_geojson(
         let trees = compartment.trees_in_bounding_box(min_x, max_x, min_y, max_y);
 
         // Convert the compartment (polygon) to a GeoJSON feature
-        let polygon_feature = convert_polygon_to_feature(&compartment.polygon);
+        let mut properties = compartment_properties(&compartment.stand_number, compartment.real_estate_id, compartment.parcel_number);
+        compartment.biomass.to_properties(&mut properties);
+        properties.insert("special_features".to_string(), serde_json::json!(compartment.special_feature_codes));
+        let polygon_feature = convert_polygon_to_feature(&compartment.polygon, Some(properties));
         let tree_features: Vec<Feature> = trees.iter().map(|tree| convert_tree_to_feature(tree)).collect();
 
         // Add the polygon feature and tree features to the list
         all_features.push(polygon_feature);
         all_features.extend(tree_features);
+        all_features.extend(dead_tree_features(&compartment.dead_trees, &compartment.stand_number));
     }
 
     // Add building features to the list, ensuring the GeoJson is a FeatureCollection
@@ -108,10 +178,12 @@ pub fn all_compartment_areas_to_geojson(
 
     for compartment_area in compartment_areas {
         // Convert the compartment (polygon) to a GeoJSON feature
-        let polygon_feature = convert_polygon_to_feature(&compartment_area.polygon);
+        let properties = compartment_properties(&compartment_area.stand_number, compartment_area.real_estate_id, compartment_area.parcel_number);
+        let polygon_feature = convert_polygon_to_feature(&compartment_area.polygon, Some(properties));
 
-        // Add the polygon feature to the list
+        // Add the polygon feature and the dead wood to the list
         all_features.push(polygon_feature);
+        all_features.extend(dead_tree_features(&compartment_area.dead_trees, &compartment_area.stand_number));
     }
 
     // Add building features to the list, ensuring the GeoJson is a FeatureCollection
@@ -148,14 +220,19 @@ pub fn all_compartment_areas_to_geojson(
 }
 
 pub fn polygon_to_geojson(polygon: &Polygon<f64>, trees: &Vec<Tree>) -> GeoJson {
+    polygons_to_geojson(std::slice::from_ref(polygon), trees)
+}
+
+// Like `polygon_to_geojson`, with a feature for every part of a stand
+pub fn polygons_to_geojson(polygons: &[Polygon<f64>], trees: &[Tree]) -> GeoJson {
     let mut all_features = Vec::new();
 
-    // Convert the compartment (polygon) to a GeoJSON feature
-    let polygon_feature = convert_polygon_to_feature(&polygon);
-    let tree_features: Vec<Feature> = trees.iter().map(|tree| convert_tree_to_feature(tree)).collect();
+    // Convert the compartment (polygons) to GeoJSON features
+    let polygon_features = polygons.iter().map(|polygon| convert_polygon_to_feature(polygon, None));
+    let tree_features: Vec<Feature> = trees.iter().map(convert_tree_to_feature).collect();
 
-    // Add the polygon feature and tree features to the list
-    all_features.push(polygon_feature);
+    // Add the polygon features and tree features to the list
+    all_features.extend(polygon_features);
     all_features.extend(tree_features);
 
     // Create the GeoJSON FeatureCollection
@@ -1,12 +1,33 @@
 use crate::forest_property::tree_stand_data::TreeStrata;
+use crate::forest_property::diameter_distribution::stratum_trees;
 use crate::forest_property::tree::Tree;
-use crate::jittered_hexagonal_sampling::{GridOptions, JitteredHexagonalGridSampling};
+use crate::forest_property::codes::Storey;
+use crate::sampling::{fill_to_target, CompleteSpatialRandomness, FillReport, FillStats, PointIndex, Sampler, SamplingOptions, StratumFill};
 use crate::projection::{Projection, CRS};
 
 use geo_types::Polygon;
-use geo::{BoundingRect, Coord, LineString};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use geo::{Area, BooleanOps, BoundingRect, Contains, Coord, LineString, Rect};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use core::f32::consts::PI;
+use std::borrow::Borrow;
+
+// Clips a polygon to a bounding box. Returns the first part of the intersection, or None if it is empty.
+pub fn clip_to_bounding_box(polygon: &Polygon<f64>, bbox: &Polygon<f64>) -> Option<Polygon<f64>> {
+    polygon.intersection(bbox).0
+        .into_iter()
+        .find(|part| part.unsigned_area() > 0.0)
+}
+
+// Clips polygons, e.g. the parts of a stand, to a bounding box. Returns every part of the intersections with an area.
+pub fn clip_polygons_to_bounding_box(polygons: &[Polygon<f64>], bbox: &Polygon<f64>) -> Vec<Polygon<f64>> {
+    polygons
+        .iter()
+        .flat_map(|polygon| polygon.intersection(bbox).0)
+        .filter(|part| part.unsigned_area() > 0.0)
+        .collect()
+}
 
 // Get minimum and maximum x and y coordinates of a polygon
 pub fn get_min_max_coordinates(p: &Polygon<f64>) -> (f64, f64, f64, f64) {
@@ -19,6 +40,25 @@ pub fn get_min_max_coordinates(p: &Polygon<f64>) -> (f64, f64, f64, f64) {
     (min_x, max_x, min_y, max_y)
 }
 
+// Get the bounding box that contains all the polygons
+pub fn bounding_box_of_polygons<I>(polygons: I) -> Option<Polygon<f64>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<Polygon<f64>>,
+{
+    let rect = polygons
+        .into_iter()
+        .filter_map(|polygon| polygon.borrow().bounding_rect())
+        .reduce(|acc, rect| {
+            Rect::new(
+                Coord { x: acc.min().x.min(rect.min().x), y: acc.min().y.min(rect.min().y) },
+                Coord { x: acc.max().x.max(rect.max().x), y: acc.max().y.max(rect.max().y) },
+            )
+        })?;
+
+    Some(rect.to_polygon())
+}
+
 pub fn generate_radius(total_stem_count: u32, area: f32) -> f32 {
     let total_trees = total_stem_count as f32 * area / 10000.0;
 
@@ -34,17 +74,99 @@ pub fn generate_radius(total_stem_count: u32, area: f32) -> f32 {
     (tree_needed_area / PI).sqrt()
 }
 
-// Generates random trees for all strata with jittered grid sampling
+// Base seed used when none is given, so that the same stand always gets the same trees
+pub const DEFAULT_SEED: u64 = 0;
+
+// Mixes a key into a seed so that consecutive keys give unrelated seeds (SplitMix64 finaliser)
+pub fn mix_seed(seed: u64, key: u64) -> u64 {
+    let mut z = seed ^ key.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Seed of a stand derived from the base seed and the stand's id. The id is hashed with FNV-1a,
+// which unlike the std hashers is stable across Rust versions and platforms.
+pub fn stand_seed(seed: u64, stand_id: &str) -> u64 {
+    let hash = stand_id.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    mix_seed(seed, hash)
+}
+
+// Seed of the stratum at `index` of a stand
+pub fn stratum_seed(stand_seed: u64, index: usize) -> u64 {
+    mix_seed(stand_seed, index as u64)
+}
+
+// Generates random trees for all strata with jittered grid sampling and `DEFAULT_SEED`
 pub fn generate_random_trees(p: &Polygon, strata: &TreeStrata, area_ratio: f64) -> Vec<Tree> {
+    generate_random_trees_with_seed(p, strata, area_ratio, DEFAULT_SEED)
+}
+
+// Generates random trees for all strata with jittered grid sampling. Every stratum gets its own
+// generator seeded from `seed` and its index, so the trees don't depend on thread scheduling.
+pub fn generate_random_trees_with_seed(p: &Polygon, strata: &TreeStrata, area_ratio: f64, seed: u64) -> Vec<Tree> {
+    generate_random_trees_with_sampling(p, strata, area_ratio, seed, &SamplingOptions::default(), "")
+}
+
+// Degrees per metre used to scale tree spacings to WGS84 coordinates
+const DEGREES_PER_METRE: f64 = 0.00001;
+// Metres per degree of latitude. A degree of longitude is shorter by the cosine of the latitude.
+pub(crate) const METRES_PER_DEGREE: f64 = 111_320.0;
+// Candidates sampled per tree of a stratum in joint sampling, as some are rejected for being too close
+const JOINT_OVERSAMPLING: f64 = 1.5;
+// Random candidates per missing tree when topping up a stratum in joint sampling
+const JOINT_TOP_UP_CANDIDATES: usize = 10;
+
+// Generates random trees for all strata with the sampling method of the stand `stand_id` and each stratum's species
+pub fn generate_random_trees_with_sampling(
+    p: &Polygon,
+    strata: &TreeStrata,
+    area_ratio: f64,
+    seed: u64,
+    sampling: &SamplingOptions,
+    stand_id: &str
+) -> Vec<Tree> {
+    generate_random_trees_with_report(p, strata, area_ratio, seed, sampling, stand_id).0
+}
+
+// Like `generate_random_trees_with_sampling`, with the target and achieved tree counts of every stratum.
+// Strata are filled adaptively, so the counts fall short only in degenerate polygons or when
+// the joint minimum distance leaves no room.
+pub fn generate_random_trees_with_report(
+    p: &Polygon,
+    strata: &TreeStrata,
+    area_ratio: f64,
+    seed: u64,
+    sampling: &SamplingOptions,
+    stand_id: &str
+) -> (Vec<Tree>, FillReport) {
+    let (trees, report) = generate_stratum_trees_with_report(p, strata, area_ratio, seed, sampling, stand_id);
+    (trees.into_iter().flatten().collect(), report)
+}
+
+// Like `generate_random_trees_with_report`, with the trees of each stratum kept apart in the order of the strata
+pub fn generate_stratum_trees_with_report(
+    p: &Polygon,
+    strata: &TreeStrata,
+    area_ratio: f64,
+    seed: u64,
+    sampling: &SamplingOptions,
+    stand_id: &str
+) -> (Vec<Vec<Tree>>, FillReport) {
     let total_stem_count = strata.tree_stratum.iter().fold(0, |mut acc: u32, f| {
         acc += f.stem_count;
         acc
     });
+    let oversampling = if sampling.joint_min_distance.is_some() { JOINT_OVERSAMPLING } else { 1.0 };
 
-    let trees = strata
+    // Points of every stratum, with how filling went and the generator to continue with
+    let mut samples: Vec<(Vec<[f64; 2]>, FillStats, StdRng)> = strata
         .tree_stratum
         .par_iter()
-        .map(|stratum| {
+        .enumerate()
+        .map(|(index, stratum)| {
             let tree_amount = (stratum.stem_count as f64) * area_ratio;
             let amount = tree_amount.round() as u32;
 
@@ -53,40 +175,103 @@ pub fn generate_random_trees(p: &Polygon, strata: &TreeStrata, area_ratio: f64)
                 stratum.basal_area
             );
             
-            radius *= 0.00001;
+            radius *= DEGREES_PER_METRE as f32;
 
-            // Jittered Grid Version 2
-            let rng = rand::thread_rng();
-            let options = GridOptions {
-                polygon: p.to_owned(),
-                radius: (radius).into(),
-                jitter: Some(0.6666),
-                point_limit: Some(amount as usize),
-            };
+            let mut rng = StdRng::seed_from_u64(stratum_seed(seed, index));
+            let sampler = sampling.method(stand_id, stratum.tree_species);
+            let limit = (amount as f64 * oversampling).ceil() as usize;
+            let (points, mut stats) = fill_to_target(sampler, p, radius.into(), limit, &mut rng);
+            stats.target = amount as usize;
+            stats.achieved = points.len().min(stats.target);
 
-            let mut grid = JitteredHexagonalGridSampling::new(rng, options);
+            (points, stats, rng)
+        })
+        .collect();
 
-            let points =  grid.fill();
+    if let Some(min_distance) = sampling.joint_min_distance {
+        thin_storeys(p, strata, &mut samples, min_distance);
+    }
+
+    let report = FillReport {
+        strata: strata.tree_stratum
+            .iter()
+            .zip(&samples)
+            .enumerate()
+            .map(|(index, (stratum, (_, stats, _)))| StratumFill {
+                index,
+                tree_species: stratum.tree_species,
+                storey: stratum.storey,
+                stats: *stats,
+            })
+            .collect(),
+    };
 
-            if points.len() == 0 {
-                //println!("\tNo trees generated for stratum with basal area {}, stem count {}, mean height {}", stratum.basal_area, stratum.stem_count, stratum.mean_height);
+    let trees = strata
+        .tree_stratum
+        .par_iter()
+        .zip(samples)
+        .map(|(stratum, (points, _, mut rng))| stratum_trees(stratum, &points, &mut rng))
+        .collect();
+
+    (trees, report)
+}
+
+// Keeps the points of each storey that are at least `min_distance` metres apart, up to the target
+// of each stratum. The strata of a storey take turns so that none of them is favoured, and strata
+// left short are topped up with random points that keep the distance. Distances are measured with
+// degrees of longitude scaled by the cosine of the polygon's mean latitude.
+fn thin_storeys(p: &Polygon, strata: &TreeStrata, samples: &mut [(Vec<[f64; 2]>, FillStats, StdRng)], min_distance: f64) {
+    let Some(rect) = p.bounding_rect() else {
+        return;
+    };
+    let latitude = rect.center().y.to_radians();
+    let scale = [METRES_PER_DEGREE * latitude.cos(), METRES_PER_DEGREE];
+
+    let mut storeys: Vec<Storey> = strata.tree_stratum.iter().map(|stratum| stratum.storey).collect();
+    storeys.sort_by_key(|storey| storey.code());
+    storeys.dedup();
+
+    for storey in storeys {
+        let members: Vec<usize> = (0..samples.len()).filter(|&i| strata.tree_stratum[i].storey == storey).collect();
+        let rounds = members.iter().map(|&i| samples[i].0.len()).max().unwrap_or(0);
+        let mut index = PointIndex::with_scale(min_distance, scale);
+        let mut accepted: Vec<Vec<[f64; 2]>> = vec![Vec::new(); members.len()];
+
+        for round in 0..rounds {
+            for (member, &i) in members.iter().enumerate() {
+                let (points, stats, _) = &samples[i];
+                if let Some(&point) = points.get(round) {
+                    if accepted[member].len() < stats.target && index.try_insert(point) {
+                        accepted[member].push(point);
+                    }
+                }
             }
-            else if points.len() < amount as usize {
-                println!("Generated {} / {} trees for stratum with basal area {}, stem count {}, mean height {}.", points.len(), amount, stratum.basal_area, stratum.stem_count, stratum.mean_height);
+        }
+
+        for (&i, mut points) in members.iter().zip(accepted) {
+            let (_, stats, rng) = &mut samples[i];
+            let missing = stats.target - points.len();
+            if missing > 0 {
+                stats.fallback = true;
+                let candidates = CompleteSpatialRandomness.sample(p, min_distance / METRES_PER_DEGREE, missing * JOINT_TOP_UP_CANDIDATES, rng);
+                points.extend(candidates.into_iter().filter(|&point| index.try_insert(point)).take(missing));
             }
+            stats.achieved = points.len();
+            samples[i].0 = points;
+        }
+    }
+}
 
-            let trees_strata: Vec<Tree> = points.iter().map(|pair: &[f64; 2]| {
-                Tree::new(
-                    stratum.tree_species,
-                    stratum.mean_height,
-                    (pair[0], pair[1], 0.0),
-                )
-            }).collect();
-            trees_strata
+// Trees whose position is inside the polygon
+pub fn clip_trees(trees: &[Tree], polygon: &Polygon) -> Vec<Tree> {
+    trees
+        .iter()
+        .filter(|tree| {
+            let (x, y, _) = tree.position();
+            polygon.contains(&Coord { x, y })
         })
-        .flatten();
-
-    trees.collect()
+        .copied()
+        .collect()
 }
 
 pub fn polygon_to_wgs84(p: &Polygon) -> Polygon {
@@ -102,4 +287,73 @@ pub fn polygon_to_wgs84(p: &Polygon) -> Polygon {
     }
 
     Polygon::new(LineString::from(coords), vec![])
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_seeded_tree_generation() {
+    use crate::forest_property::tree_stand_data::TreeStratum;
+
+    let polygon = Polygon::new(
+        LineString::from(vec![(25.0, 65.0), (25.002, 65.0), (25.002, 65.001), (25.0, 65.001), (25.0, 65.0)]),
+        vec![],
+    );
+    let stratum = |stem_count: u32, basal_area: f32| TreeStratum {
+        stem_count,
+        basal_area,
+        mean_diameter: 20.0,
+        mean_height: 18.0,
+        ..Default::default()
+    };
+    let strata = TreeStrata::new(vec![stratum(600, 12.0), stratum(300, 8.0)]);
+    let positions = |seed: u64| -> Vec<(f64, f64, f64)> {
+        generate_random_trees_with_seed(&polygon, &strata, 1.0, seed).iter().map(|tree| tree.position()).collect()
+    };
+
+    assert!(!positions(1).is_empty());
+    assert_eq!(positions(1), positions(1));
+    assert_ne!(positions(1), positions(2));
+    let unseeded: Vec<(f64, f64, f64)> = generate_random_trees(&polygon, &strata, 1.0).iter().map(|tree| tree.position()).collect();
+    assert_eq!(unseeded, positions(DEFAULT_SEED));
+    assert_eq!(stand_seed(7, "2554724"), stand_seed(7, "2554724"));
+    assert_ne!(stand_seed(7, "2554724"), stand_seed(7, "2554725"));
+}
+
+#[test]
+fn test_joint_storey_sampling() {
+    use crate::forest_property::codes::TreeSpecies;
+    use crate::forest_property::tree_stand_data::TreeStratum;
+
+    let polygon = Polygon::new(
+        LineString::from(vec![(25.0, 65.0), (25.002, 65.0), (25.002, 65.001), (25.0, 65.001), (25.0, 65.0)]),
+        vec![],
+    );
+    let stratum = |storey: Storey, tree_species: TreeSpecies| TreeStratum {
+        storey,
+        tree_species,
+        stem_count: 400,
+        basal_area: 10.0,
+        mean_diameter: 20.0,
+        mean_height: 18.0,
+        ..Default::default()
+    };
+    let strata = TreeStrata::new(vec![
+        stratum(Storey::Dominant, TreeSpecies::ScotsPine),
+        stratum(Storey::Dominant, TreeSpecies::SilverBirch),
+        stratum(Storey::Undergrowth, TreeSpecies::NorwaySpruce),
+    ]);
+    let sampling = SamplingOptions { joint_min_distance: Some(2.0), ..Default::default() };
+    let trees = generate_random_trees_with_sampling(&polygon, &strata, 1.0, 5, &sampling, "1");
+
+    let dominant: Vec<(f64, f64, f64)> = trees.iter()
+        .filter(|tree| tree.species() != TreeSpecies::NorwaySpruce)
+        .map(|tree| tree.position())
+        .collect();
+    assert!(dominant.len() > 400 && trees.len() > dominant.len());
+    // Distances in metres, with degrees of longitude shorter by the cosine of the latitude
+    let metres_per_longitude = METRES_PER_DEGREE * 65.0005_f64.to_radians().cos();
+    for (i, a) in dominant.iter().enumerate() {
+        for b in &dominant[i + 1..] {
+            assert!(((a.0 - b.0) * metres_per_longitude).hypot((a.1 - b.1) * METRES_PER_DEGREE) >= 2.0 - 1e-9);
+        }
+    }
+}
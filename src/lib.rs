@@ -1,7 +1,9 @@
+pub mod error;
 pub mod forest_property;
 pub mod geometry_utils;
 pub mod geojson_utils;
 pub mod jittered_hexagonal_sampling;
+pub mod sampling;
 pub mod projection;
 pub mod main_functions;
 
@@ -1,9 +1,13 @@
 use std::fs::File;
-use crate::geometry_utils::{generate_random_trees, get_min_max_coordinates};
-use crate::geojson_utils::{polygon_to_geojson, all_compartments_to_geojson};
-use crate::forest_property::compartment::get_compartments_in_bounding_box;
+use crate::geometry_utils::{bounding_box_of_polygons, get_min_max_coordinates};
+use crate::geojson_utils::{add_layer, polygons_to_geojson, all_compartments_to_geojson};
+use crate::forest_property::codes::TreeSpecies;
+use crate::forest_property::compartment::{find_stands_in_bounding_box, get_compartments_in_bounding_box, GenerationOptions, StandTrees};
+use crate::forest_property::dead_wood::clip_dead_trees;
 use crate::forest_property::forest_property_data::ForestPropertyData;
 use crate::forest_property::image_processor::ImageProcessor;
+use crate::forest_property::special_features::special_features_to_geojson;
+use crate::forest_property::stand_reader::StandReader;
 use geo::{coord, Coord, LineString, Polygon};
 use geojson::GeoJson;
 use image::Rgb;
@@ -12,47 +16,21 @@ use std::io::Write;
 use std::time::Instant;
 use std::error::Error;
 
+// Color of snags and fallen logs
+const DEAD_WOOD_COLOR: Rgb<u8> = Rgb([139, 69, 19]);
+// Color and pixel spacing of the hatching of stands with special features
+const SPECIAL_FEATURE_COLOR: Rgb<u8> = Rgb([192, 57, 155]);
+const HATCH_SPACING: u32 = 6;
 
 // Get the bounding box of the whole map
 pub fn get_bounding_box_of_map() -> Polygon<f64> {
-    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
-    let mut all_stands = property.real_estates.real_estate[0].get_stands();
-
-    let mut min_x = f64::MAX;
-    let mut max_x = f64::MIN;
-    let mut min_y = f64::MAX;
-    let mut max_y = f64::MIN;
+    // Stream the stands so that the whole file doesn't need to fit in memory
+    let polygons = StandReader::from_file("forestpropertydata.xml")
+        .expect("Could not read the XML file")
+        .stands()
+        .flat_map(|stand| stand.expect("Could not parse the XML").polygons().to_vec());
 
-    for stand in all_stands.iter_mut() {
-        let polygon = stand.computed_polygon.to_owned().unwrap();
-        let (p_min_x, p_max_x, p_min_y, p_max_y) = get_min_max_coordinates(&polygon);
-
-        if p_min_x < min_x {
-            min_x = p_min_x;
-        }
-        if p_max_x > max_x {
-            max_x = p_max_x;
-        }
-        if p_min_y < min_y {
-            min_y = p_min_y;
-        }
-        if p_max_y > max_y {
-            max_y = p_max_y;
-        }
-    }
-    
-    let bbox = geo::Polygon::new(
-        LineString(vec![
-            Coord { x: min_x, y: min_y },
-            Coord { x: max_x, y: min_y },
-            Coord { x: max_x, y: max_y },
-            Coord { x: min_x, y: max_y },
-            Coord { x: min_x, y: min_y },
-        ]),
-        vec![],
-    );
-
-    bbox
+    bounding_box_of_polygons(polygons).expect("No stands found")
 }
 
 pub fn random_bbox(map_bbox: &Polygon<f64>) -> Polygon<f64> {
@@ -79,9 +57,9 @@ pub fn random_bbox(map_bbox: &Polygon<f64>) -> Polygon<f64> {
     random_bbox
 }
 
-// Get color based on species number
-fn get_color_by_species(number: u8) -> Rgb<u8> {
-    match number {
+// Get color based on species
+fn get_color_by_species(species: TreeSpecies) -> Rgb<u8> {
+    match species.code() {
         // Coniferous Trees (Shades of Orange and Red)
         1 => Rgb([255, 165, 0]),    // Orange - Mänty
         2 => Rgb([255, 0, 0]),      // Red - Kuusi
@@ -135,15 +113,16 @@ pub fn create_geo_json_from_coords(min_x: f64, max_x: f64, min_y: f64, max_y: f6
         vec![],
     );
 
-    let real_estate = property.real_estates.real_estate[0].clone();
-    let stands = real_estate.get_stands();
+    let stands = property.get_stands();
     println!("Total stands: {:?}", stands.len());
+    let special_features = special_features_to_geojson(find_stands_in_bounding_box(&stands, &bbox).unwrap_or_default());
 
     // Create compartments in the bounding box
     let compartments = get_compartments_in_bounding_box(stands, &bbox);
     println!("\nCompartments in bounding box: {:?}", compartments.len());
 
-    let geojson = all_compartments_to_geojson(compartments, &buildings_geojson, &roads_geojson);
+    let mut geojson = all_compartments_to_geojson(compartments, &buildings_geojson, &roads_geojson);
+    add_layer(&mut geojson, special_features);
 
     let duration = start.elapsed();
     println!("\nTime elapsed in create_geo_json_for_bbox is: {:?}\n", duration);
@@ -155,8 +134,7 @@ pub fn create_geo_json_from_coords(min_x: f64, max_x: f64, min_y: f64, max_y: f6
 pub fn draw_stands_in_bbox(bbox: &Polygon<f64>, property: &ForestPropertyData, buildings: &Vec<Polygon>) -> ImageProcessor {
     let start = Instant::now();
 
-    let real_estate = property.real_estates.real_estate[0].clone();
-    let stands = real_estate.get_stands();
+    let stands = property.get_stands();
     println!("Total stands: {:?}\n", stands.len());
 
     // Find compartments in the bounding box
@@ -182,9 +160,13 @@ pub fn draw_stands_in_bbox(bbox: &Polygon<f64>, property: &ForestPropertyData, b
         let (min_x, max_x, min_y, max_y) = get_min_max_coordinates(&polygon);
         let trees = compartment.trees_in_bounding_box(min_x, max_x, min_y, max_y);
 
-        // Draw the clipped polygon
+        // Draw the clipped polygon, hatched if the stand has special features
         let mapped_coordinates = image.map_coordinates_to_image(&polygon, &scale);
-        image.draw_polygon_image(&mapped_coordinates, Rgb([0, 0, 255]));
+        if compartment.special_feature_codes.is_empty() {
+            image.draw_polygon_image(&mapped_coordinates, Rgb([0, 0, 255]));
+        } else {
+            image.draw_hatched_polygon(&mapped_coordinates, SPECIAL_FEATURE_COLOR, HATCH_SPACING);
+        }
 
         // Draw the trees
         for tree in trees {
@@ -192,6 +174,11 @@ pub fn draw_stands_in_bbox(bbox: &Polygon<f64>, property: &ForestPropertyData, b
             let color = get_color_by_species(tree.species());
             image.draw_random_point(&scale, img_width, img_height, point, color);
         }
+
+        // Draw the dead wood
+        for dead_tree in clip_dead_trees(&compartment.dead_trees, &polygon) {
+            image.draw_dead_tree(&scale, &dead_tree, DEAD_WOOD_COLOR);
+        }
     }
 
     // Draw the buildings
@@ -209,27 +196,32 @@ pub fn draw_stands_in_bbox(bbox: &Polygon<f64>, property: &ForestPropertyData, b
 /* ASKS USER FOR STAND AND DRAWS STAND. SAVES STAND TO GEOJSON */
 pub fn draw_selected_stand(property: &ForestPropertyData) -> ImageProcessor {
     let mut stand = property.get_stand_cli();
-    let polygon = stand.create_polygon();
+    stand.compute_polygon();
+    let polygons = stand.polygons().to_vec();
 
     // Create an image for the polygon and random points
     let img_width = 800;
     let img_height = 600;
     let mut image = ImageProcessor::new(img_width, img_height);
 
-    // Get the minimum and maximum x and y coordinates of the polygon
-    let (min_x, max_x, min_y, max_y) = get_min_max_coordinates(&polygon);
+    // Get the minimum and maximum x and y coordinates of every part of the stand
+    let bounding_box = bounding_box_of_polygons(&polygons).expect("Stand has no polygon");
+    let (min_x, max_x, min_y, max_y) = get_min_max_coordinates(&bounding_box);
     let scale = ImageProcessor::create_scale(min_x, max_x, min_y, max_y, img_width, img_height);
 
     // Map polygon coordinates to image
-    let mapped_coordinates = image.map_coordinates_to_image(&polygon, &scale);
-    image.draw_polygon_image(&mapped_coordinates, Rgb([0, 0, 255]));
+    for polygon in &polygons {
+        let mapped_coordinates = image.map_coordinates_to_image(polygon, &scale);
+        image.draw_polygon_image(&mapped_coordinates, Rgb([0, 0, 255]));
+    }
 
     let summary_stem_count = stand.summary_stem_count();
-    let strata = stand.get_strata().expect("No treeStrata/stratums found");
-    let random_trees = generate_random_trees(&polygon, &strata, 1.0);
+    stand.get_strata().expect("No treeStrata/stratums found");
+    // Same trees as the stand gets in the bounding box queries
+    let random_trees = StandTrees::generate(&stand, &GenerationOptions::default()).trees;
 
-    // Convert the Polygon and the trees to GeoJSON
-    let geojson = polygon_to_geojson(&polygon, &random_trees);
+    // Convert the polygons and the trees to GeoJSON
+    let geojson = polygons_to_geojson(&polygons, &random_trees);
 
     // Serialize GeoJson to a String
     let geojson_string = serde_json::to_string_pretty(&geojson).expect("Failed to serialize GeoJson");
@@ -272,3 +264,64 @@ pub fn save_geojson(geojson: &GeoJson, filename: &str) {
 
     println!("GeoJSON saved to {}", filename);
 }
+
+#[test]
+fn test_draw_dead_wood() {
+    use geo::BoundingRect;
+
+    let has_dead_wood = |data_date: &crate::forest_property::tree_stand_data::TreeStandDataDate| {
+        data_date.dead_tree_strata.as_ref().is_some_and(|strata| strata.dead_tree_stratum.iter().any(|stratum| stratum.volume > 0.0))
+    };
+
+    // Dead wood is only inventoried in the older snapshots, copy it to the latest one of a stand
+    let mut property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stand = property.real_estates.real_estate.iter_mut()
+        .flat_map(|real_estate| real_estate.parcels.parcel.iter_mut())
+        .flat_map(|parcel| parcel.stands.stand.iter_mut())
+        .filter(|stand| stand.tree_stand_data_dates().iter().any(has_dead_wood))
+        .min_by(|a, b| a.stand_basic_data.area.total_cmp(&b.stand_basic_data.area))
+        .unwrap();
+    let data_dates = &mut stand.tree_stand_data.as_mut().unwrap().tree_stand_data_date;
+    let dead_tree_strata = data_dates.iter().find(|data_date| has_dead_wood(data_date)).unwrap().dead_tree_strata.clone();
+    data_dates.iter_mut().for_each(|data_date| data_date.dead_tree_strata = dead_tree_strata.clone());
+    let stand_id = stand.id.clone();
+
+    let stands = property.get_stands();
+    let stand = stands.iter().find(|stand| stand.id == stand_id).unwrap();
+    let rect = stand.computed_polygon.as_ref().unwrap().bounding_rect().unwrap();
+
+    let image = draw_stands_in_bbox(&rect.to_polygon(), &property, &vec![]);
+    assert!(image.img().pixels().any(|pixel| *pixel == DEAD_WOOD_COLOR));
+
+    let empty = GeoJson::FeatureCollection(geojson::FeatureCollection { features: vec![], bbox: None, foreign_members: None });
+    let geojson = create_geo_json_from_coords(rect.min().x, rect.max().x, rect.min().y, rect.max().y, &property, &empty, &empty).unwrap();
+    let GeoJson::FeatureCollection(collection) = geojson else { panic!("Expected a feature collection") };
+    assert!(collection.features.iter().any(|feature| feature.contains_property("dead_tree_type")));
+}
+
+#[test]
+fn test_draw_special_features() {
+    use geo::BoundingRect;
+
+    let property = ForestPropertyData::from_xml_file("forestpropertydata.xml");
+    let stands = property.get_stands();
+    let stand = stands.iter()
+        .filter(|stand| stand.computed_polygon.is_some() && !stand.special_features().is_empty())
+        .min_by(|a, b| a.stand_basic_data.area.total_cmp(&b.stand_basic_data.area))
+        .unwrap();
+    let rect = stand.computed_polygon.as_ref().unwrap().bounding_rect().unwrap();
+
+    // The stand is hatched, not only outlined
+    let image = draw_stands_in_bbox(&rect.to_polygon(), &property, &Vec::new());
+    let width = image.img().width();
+    let hatched_rows = (0..image.img().height())
+        .filter(|&y| (0..width).filter(|&x| *image.img().get_pixel(x, y) == SPECIAL_FEATURE_COLOR).count() > 2)
+        .count();
+    assert!(hatched_rows > image.img().height() as usize / 2);
+
+    let empty = GeoJson::FeatureCollection(geojson::FeatureCollection { features: vec![], bbox: None, foreign_members: None });
+    let geojson = create_geo_json_from_coords(rect.min().x, rect.max().x, rect.min().y, rect.max().y, &property, &empty, &empty).unwrap();
+    let GeoJson::FeatureCollection(collection) = geojson else { panic!("Expected a feature collection") };
+    let feature_code = stand.special_features()[0].feature_code.trim();
+    assert!(collection.features.iter().any(|feature| feature.property("feature_code") == Some(&serde_json::json!(feature_code))));
+}
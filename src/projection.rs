@@ -6,12 +6,16 @@ pub struct Projection {
     to: Proj
 }
 
-pub const EPSG_3067: &'static str  = "+proj=utm +zone=35 +ellps=GRS80 +towgs84=0,0,0,0,0,0,0 +units=m +no_defs +type=crs";
-pub const EPSG_4326: &'static str  = "+proj=longlat +datum=WGS84 +no_defs +type=crs";
+pub const EPSG_3067: &str  = "+proj=utm +zone=35 +ellps=GRS80 +towgs84=0,0,0,0,0,0,0 +units=m +no_defs +type=crs";
+pub const EPSG_4326: &str  = "+proj=longlat +datum=WGS84 +no_defs +type=crs";
+// KKJ / Finland Uniform Coordinate System (YKJ), used in older forest data
+pub const EPSG_2393: &str  = "+proj=tmerc +lat_0=0 +lon_0=27 +k=1 +x_0=3500000 +y_0=0 +ellps=intl +towgs84=-96.062,-82.428,-121.753,4.801,0.345,-1.376,1.496 +units=m +no_defs +type=crs";
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CRS {
     Epsg3067,
-    Epsg4326
+    Epsg4326,
+    Epsg2393
 }
 
 impl CRS {
@@ -19,11 +23,42 @@ impl CRS {
     fn proj_str(&self) -> &str {
 
         match &self {
-            CRS::Epsg3067 => &EPSG_3067,
-            CRS::Epsg4326 => &EPSG_4326
+            CRS::Epsg3067 => EPSG_3067,
+            CRS::Epsg4326 => EPSG_4326,
+            CRS::Epsg2393 => EPSG_2393
         }
 
     }
+
+    // Reads the CRS from a GML srsName, e.g. `EUREF-FIN`, `EPSG:3067` or `urn:ogc:def:crs:EPSG::3067`
+    pub fn from_srs_name(srs_name: &str) -> Option<CRS> {
+        let name = srs_name.trim().to_uppercase();
+
+        match name.as_str() {
+            "EUREF-FIN" | "ETRS-TM35FIN" | "ETRS89 / TM35FIN(E,N)" => return Some(CRS::Epsg3067),
+            "WGS84" | "WGS 84" | "CRS:84" => return Some(CRS::Epsg4326),
+            "KKJ" | "YKJ" => return Some(CRS::Epsg2393),
+            _ => {}
+        }
+
+        // EPSG code is the last number of the name in all of the EPSG notations
+        let code = name.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+        if !name.contains("EPSG") {
+            return None;
+        }
+        match code {
+            "3067" => Some(CRS::Epsg3067),
+            "4326" => Some(CRS::Epsg4326),
+            "2393" => Some(CRS::Epsg2393),
+            _ => None,
+        }
+    }
+
+    // The URN and HTTP forms of EPSG:4326 have latitude first
+    pub fn has_latitude_first(srs_name: &str) -> bool {
+        let name = srs_name.to_uppercase();
+        CRS::from_srs_name(srs_name) == Some(CRS::Epsg4326) && (name.starts_with("URN:") || name.starts_with("HTTP://WWW.OPENGIS.NET/DEF/"))
+    }
 }
 
 impl Projection {
@@ -49,12 +84,20 @@ impl Projection {
 
     pub fn transform(&self, x:f64, y:f64) -> (f64, f64) {
 
-        let mut point_3d = (x, y, 0.0);
+        // Note that angular unit is radians, not degrees
+        let mut point_3d = if self.from.is_latlong() {
+            (x.to_radians(), y.to_radians(), 0.0)
+        } else {
+            (x, y, 0.0)
+        };
 
         proj4rs::transform::transform(&self.from, &self.to, &mut point_3d).unwrap();
-    
-        // Note that angular unit is radians, not degrees
-        (point_3d.0.to_degrees(), point_3d.1.to_degrees())
+
+        if self.to.is_latlong() {
+            (point_3d.0.to_degrees(), point_3d.1.to_degrees())
+        } else {
+            (point_3d.0, point_3d.1)
+        }
     }
 
     pub fn transform_back(&self, x:f64, y:f64) -> (f64, f64) {
@@ -75,7 +118,7 @@ impl fmt::Debug for Projection {
 }
 
 impl PartialEq for Projection {
-    fn eq(&self, other: &Self) -> bool {
+    fn eq(&self, _other: &Self) -> bool {
         //self.from == other.from && self.to == other.to
         true
     }
@@ -138,14 +181,14 @@ fn test_projection() {
 #[test]
 fn test_projection_impl() {
     // EPSG:3067 - TM35FIN(E,N) -- Finland
-    let proj = Projection::new(CRS::Epsg3067, CRS::Epsg3067);
+    let proj = Projection::new(CRS::Epsg3067, CRS::Epsg4326);
 
     
     /*  N=7369564.333, E=427997.035 */
     let epsg3067_northern = 7369564.333;
     let epsg3067_eastern = 427997.035;
 
-    let (lon, lat) = proj.transform(epsg3067_northern, epsg3067_eastern);
+    let (lon, lat) = proj.transform(epsg3067_eastern, epsg3067_northern);
 
     // Output in longitude, latitude
     println!("LatLng: {},{}", lat, lon);
@@ -157,4 +200,18 @@ fn test_projection_impl() {
 
     // EPSG:3067 E 427997.035 -> EPSG:4326 longitude 25°23'8.67" = 25.385742
     // EPSG:3067 N 7369564.333 -> EPSG:4326 latitude 66°26'13.646" = 66.437124
-}
\ No newline at end of file
+}
+#[test]
+fn test_crs_from_srs_name() {
+    assert_eq!(CRS::from_srs_name("EUREF-FIN"), Some(CRS::Epsg3067));
+    assert_eq!(CRS::from_srs_name("urn:ogc:def:crs:EPSG::3067"), Some(CRS::Epsg3067));
+    assert_eq!(CRS::from_srs_name("http://www.opengis.net/gml/srs/epsg.xml#2393"), Some(CRS::Epsg2393));
+    assert_eq!(CRS::from_srs_name("EPSG:4326"), Some(CRS::Epsg4326));
+    assert_eq!(CRS::from_srs_name("EPSG:3857"), None);
+    assert!(CRS::has_latitude_first("urn:ogc:def:crs:EPSG::4326"));
+
+    // Degrees in, degrees out
+    let proj = Projection::new(CRS::Epsg4326, CRS::Epsg4326);
+    let (lon, lat) = proj.transform(25.385742, 66.437124);
+    assert!((lon - 25.385742).abs() < 1E-9 && (lat - 66.437124).abs() < 1E-9);
+}
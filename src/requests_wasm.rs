@@ -1,14 +1,13 @@
-use crate::geometry_utils::{get_min_max_coordinates, generate_radius};
+use crate::geometry_utils::{clip_polygons_to_bounding_box, clip_trees, get_min_max_coordinates};
 use crate::forest_property::forest_property_data::ForestPropertyData;
-use crate::forest_property::tree_stand_data::TreeStrata;
-use crate::forest_property::tree::Tree;
 use crate::forest_property::stand::Stand;
-use crate::forest_property::compartment::{find_stands_in_bounding_box, CompartmentArea};
-use crate::geojson_utils::all_compartment_areas_to_geojson;
-use crate::shared_buffer::SharedBuffer;
-use crate::jittered_hexagonal_sampling::{GridOptions, JitteredHexagonalGridSampling};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use geo::{coord, Area, LineString, Polygon, BooleanOps};
+use crate::forest_property::compartment::{find_stands_in_bounding_box, CompartmentArea, GenerationOptions, StandTrees};
+use crate::forest_property::dead_wood::clip_dead_trees;
+use crate::forest_property::special_features::{special_features_to_geojson, FeatureRules};
+use crate::geojson_utils::{add_layer, all_compartment_areas_to_geojson};
+use crate::shared_buffer::{SharedBuffer, VALUES_PER_TREE};
+use crate::sampling::SamplingOptions;
+use geo::{coord, BooleanOps, LineString, Polygon};
 use geojson::{GeoJson, Value};
 use reqwest_wasm::Client;
 use reqwest::Error as ReqwestError;
@@ -101,11 +100,29 @@ pub async fn geo_json_from_coords(
     min_y: f64,
     max_y: f64,
     xml_content: String,
+    seed: Option<u64>,
+    feature_rules: Option<String>,
+    sampling: Option<String>,
 ) -> Result<JsValue, JsValue> {
     // Get the ForestPropertyData from the XML content
-    let property = ForestPropertyData::from_xml_str(&xml_content);
+    let property = ForestPropertyData::try_from_xml_str(&xml_content)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse forest property data: {}", e)))?;
     log_1(&"Got property".into());
 
+    // Rules of the special features as JSON, e.g. {"rules": [{"feature_code": "1011", "skip_tree_generation": true}]}
+    let feature_rules: FeatureRules = match feature_rules {
+        Some(feature_rules) => serde_json::from_str(&feature_rules)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse feature rules: {}", e)))?,
+        None => FeatureRules::default(),
+    };
+
+    // Sampling methods by stand and species as JSON, see `SamplingOptions`
+    let sampling: SamplingOptions = match sampling {
+        Some(sampling) => serde_json::from_str(&sampling)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse sampling options: {}", e)))?,
+        None => SamplingOptions::default(),
+    };
+
     let mut bbox = Polygon::new(
         LineString(vec![
             coord!(x: min_x, y: min_y),
@@ -159,16 +176,18 @@ pub async fn geo_json_from_coords(
     let roads_geojson: GeoJson = serde_json::from_str(&roads_text)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse roads GeoJson: {}", e)))?;
 
-    // Get the ForestPropertyData and stands
-    let real_estate = property.real_estates.real_estate[0].clone();
-    let stands = real_estate.get_stands();
+    // Get the stands of all real estates
+    let stands = property.get_stands();
+    let special_features = special_features_to_geojson(find_stands_in_bounding_box(&stands, &bbox).unwrap_or_default());
 
     // Get compartment areas in the bounding box and convert them to GeoJSON
-    let compartment_areas = get_compartment_areas_in_bounding_box(stands, &bbox);
+    let options = GenerationOptions { seed, feature_rules, sampling, ..Default::default() };
+    let compartment_areas = get_compartment_areas_in_bounding_box(stands, &bbox, &options);
     let max_tree_count = compartment_areas.1;
     let tree_count = compartment_areas.2;
     let buffer_pointer = compartment_areas.3;
-    let geojson = all_compartment_areas_to_geojson(compartment_areas.0, &buildings_geojson, &roads_geojson);
+    let mut geojson = all_compartment_areas_to_geojson(compartment_areas.0, &buildings_geojson, &roads_geojson);
+    add_layer(&mut geojson, special_features);
     log_1(&"Got geojson".into());
 
     // Create a combined struct with both the GeoJSON and tree_count
@@ -186,70 +205,29 @@ pub async fn geo_json_from_coords(
     Ok(result_js_value)
 }
 
-// Generates random trees for all strata with jittered grid sampling
+// Generates the trees of the whole stand with the shared generator and adds the ones inside `clipped`,
+// e.g. the bounding box, to the buffer. The trees depend only on the stand and the options,
+// so the same tree is at the same place in every bounding box.
 pub fn generate_random_trees_into_buffer(
-    p: &Polygon,
-    strata: &TreeStrata,
-    area_ratio: f64,
+    stand: &Stand,
+    clipped: &Polygon,
+    options: &GenerationOptions,
     buffer: &SharedBuffer, // Pass in the SharedBuffer to fill
     start_index: usize
 ) -> usize {
-    let total_stem_count = strata.tree_stratum.iter().fold(0, |mut acc: u32, f| {
-        acc += f.stem_count;
-        acc
-    });
+    let stand_trees = StandTrees::generate(stand, options);
+    for stratum in stand_trees.fill_report.incomplete() {
+        log_1(&format!("Generated {} / {} trees for stratum {} of stand {}", stratum.stats.achieved, stratum.stats.target, stratum.index, stand.id).into());
+    }
 
+    let trees = clip_trees(&stand_trees.trees, clipped);
     let mut tree_count = 0;
-
-    let trees = strata
-        .tree_stratum
-        .par_iter()
-        .map(|stratum| {
-            let tree_amount = (stratum.stem_count as f64) * area_ratio;
-            let amount = tree_amount.round() as u32;
-
-            let mut radius = generate_radius(total_stem_count, stratum.basal_area);
-            radius *= 0.00001;
-
-            // Jittered Grid Version 2
-            let rng = rand::thread_rng();
-            let options = GridOptions {
-                polygon: p.to_owned(),
-                radius: (radius).into(),
-                jitter: Some(0.6666),
-                point_limit: Some(amount as usize),
-            };
-
-            let mut grid = JitteredHexagonalGridSampling::new(rng, options);
-            let points = grid.fill();
-
-            if points.is_empty() {
-                //println!("\tNo trees generated for stratum with basal area {}, stem count {}, mean height {}", stratum.basal_area, stratum.stem_count, stratum.mean_height);
-            } else if points.len() < amount as usize {
-                println!(
-                    "Generated {} / {} trees for stratum with basal area {}, stem count {}, mean height {}.",
-                    points.len(), amount, stratum.basal_area, stratum.stem_count, stratum.mean_height
-                );
-            }
-
-            let trees_strata: Vec<Tree> = points
-                .iter()
-                .map(|pair: &[f64; 2]| {
-                    Tree::new(stratum.tree_species, stratum.mean_height, (pair[0], pair[1], 0.0))
-                })
-                .collect();
-
-            trees_strata
-        })
-        .flatten()
-        .collect::<Vec<Tree>>();
  
     // Insert the trees into the buffer
     for (i, tree) in trees.iter().enumerate() {
         let buffer_index = start_index + i;
-        if i < buffer.len() / 3 {
-            // Fill the buffer with x, y, and species
-            buffer.fill_tree(buffer_index, tree.position().0, tree.position().1, tree.species());
+        if buffer_index < buffer.capacity() {
+            buffer.fill_tree(buffer_index, tree);
             tree_count += 1;
         } else {
             break; // Avoid overflowing the buffer
@@ -263,6 +241,7 @@ pub fn generate_random_trees_into_buffer(
 pub fn get_compartment_areas_in_bounding_box(
     all_stands: Vec<Stand>,
     bbox: &Polygon,
+    options: &GenerationOptions,
 ) -> (Vec<CompartmentArea>, usize, usize, u64) {
     // Find stands in the bounding box
     let stands = find_stands_in_bounding_box(&all_stands, bbox);
@@ -271,7 +250,11 @@ pub fn get_compartment_areas_in_bounding_box(
     let mut max_tree_count = 0;
     if let Some(stands) = &stands {
         for stand in stands {
-            let strata = stand.get_strata();
+            // Stands whose special features skip tree generation get no trees
+            if options.feature_rules.zone(stand).skip_tree_generation {
+                continue;
+            }
+            let strata = stand.get_strata_for(&options.snapshot);
 
             if let Some(strata) = strata {
                 let strata_stem_count = strata.tree_stratum.iter().fold(0, |mut acc: u32, f| {
@@ -293,34 +276,31 @@ pub fn get_compartment_areas_in_bounding_box(
 
         let mut buffer_index = 0;
         for stand in stands {
-            let polygon = stand.computed_polygon.to_owned().unwrap();
-            let strata = stand.get_strata();
-
-            // Clip the stand's polygon to the bounding box
-            let intersected_polygons = polygon.intersection(bbox).0;
-            let clipped_polygon = intersected_polygons.first()
-                .expect("Intersection result should contain at least one polygon")
-                .to_owned();
-
-            // Calculate the area ratio of the clipped polygon to the original polygon
-            let original_area = polygon.unsigned_area();
-            let clipped_area = clipped_polygon.unsigned_area();
-            let area_ratio = clipped_area / original_area;
-
-            // Generate trees and save them to the buffer if strata exist
-            let mut tree_count = 0;
-            if let Some(strata) = strata {
-                tree_count = generate_random_trees_into_buffer(&clipped_polygon, &strata, area_ratio, &buffer, buffer_index);
-                buffer_index += tree_count;
-                log_1(&format!("Generated {} trees for stand {}", tree_count, stand.stand_basic_data.stand_number).into());
+            // Clip every part of the stand's polygon to the bounding box, skipping stands with invalid geometry
+            let clipped_polygons = clip_polygons_to_bounding_box(stand.polygons(), bbox);
+            if clipped_polygons.is_empty() {
+                continue;
             }
+
+            // Generate trees and save them to the buffer, the options decide the snapshot, sampling and special feature rules
+            let tree_count = generate_random_trees_into_buffer(stand, bbox, options, &buffer, buffer_index);
+            buffer_index += tree_count;
+            log_1(&format!("Generated {} trees for stand {}", tree_count, stand.stand_basic_data.stand_number).into());
             total_tree_count += tree_count;
 
-            // Add to the compartment areas list
-            compartment_areas.push(CompartmentArea {
-                stand_number: stand.stand_basic_data.stand_number.to_string(),
-                polygon: clipped_polygon,
-            });
+            // Dead wood is generated for the whole stand like in the native path, so it stays in place between bounding boxes
+            let (dead_trees, _) = StandTrees::generate_dead_trees(stand, options);
+
+            // Add every clipped part to the compartment areas list
+            for clipped_polygon in clipped_polygons {
+                compartment_areas.push(CompartmentArea {
+                    stand_number: stand.stand_basic_data.stand_number.to_string(),
+                    dead_trees: clip_dead_trees(&dead_trees, &clipped_polygon),
+                    polygon: clipped_polygon,
+                    real_estate_id: stand.real_estate_id,
+                    parcel_number: stand.parcel_number,
+                });
+            }
         }
 
         // Get a slice of the buffer
@@ -329,9 +309,12 @@ pub fn get_compartment_areas_in_bounding_box(
         };  
 
         log_1(&"Buffer contains:".into());
-        for (i, value) in buffer_slice.iter().enumerate() {
-            if i % 3 == 0 && buffer_slice[i + 2] != 0.0 {
-                let buffer_info = format!("Tree {}: x = {}, y = {}, species = {}", i / 3, buffer_slice[i], buffer_slice[i + 1], buffer_slice[i + 2]);
+        for (i, values) in buffer_slice.chunks_exact(VALUES_PER_TREE).enumerate() {
+            if values[2] != 0.0 {
+                let buffer_info = format!(
+                    "Tree {}: x = {}, y = {}, species = {}, diameter = {}, height = {}, crown radius = {}",
+                    i, values[0], values[1], values[2], values[3], values[4], values[5]
+                );
                 log_1(&buffer_info.into());
             }
         }
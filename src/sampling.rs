@@ -0,0 +1,512 @@
+use crate::forest_property::codes::{Storey, TreeSpecies};
+use crate::jittered_hexagonal_sampling::{GridOptions, JitteredHexagonalGridSampling};
+use fast_poisson::Poisson2D;
+use geo::{BoundingRect, Contains, Coord, Polygon};
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use rand_distr::{Distribution, Normal, Poisson};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+// Attempts per requested point before a sampler gives up, e.g. in a polygon that is mostly holes
+const ATTEMPTS_PER_POINT: usize = 30;
+
+/// Point process that places the trees of a stratum. `spacing` is the typical distance between
+/// neighbouring trees in the units of the polygon, and at most `limit` points are returned.
+pub trait Sampler {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]>;
+}
+
+fn contains(polygon: &Polygon, point: [f64; 2]) -> bool {
+    polygon.contains(&Coord { x: point[0], y: point[1] })
+}
+
+fn random_point_in_rect(polygon: &Polygon, rng: &mut dyn RngCore) -> Option<[f64; 2]> {
+    let rect = polygon.bounding_rect()?;
+    Some([rng.gen_range(rect.min().x..=rect.max().x), rng.gen_range(rect.min().y..=rect.max().y)])
+}
+
+// Shuffles the points so that truncating them doesn't favour a part of the polygon
+fn shuffle_and_truncate(mut points: Vec<[f64; 2]>, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+    points.shuffle(rng);
+    points.truncate(limit);
+    points
+}
+
+/// Hexagonal grid with each point moved randomly inside its cell
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct JitteredGrid {
+    /// Share of the cell radius a point can move
+    pub jitter: f64,
+}
+
+impl Default for JitteredGrid {
+    fn default() -> Self {
+        JitteredGrid { jitter: 0.6666 }
+    }
+}
+
+impl Sampler for JitteredGrid {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        let options = GridOptions {
+            polygon: polygon.to_owned(),
+            radius: spacing,
+            jitter: Some(self.jitter),
+            point_limit: Some(limit),
+        };
+        JitteredHexagonalGridSampling::new(rng, options).fill()
+    }
+}
+
+/// Poisson-disk sampling, no two points closer than `spacing`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoissonDisk;
+
+impl Sampler for PoissonDisk {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        let Some(rect) = polygon.bounding_rect() else {
+            return vec![];
+        };
+        let points = Poisson2D::new()
+            .with_dimensions([rect.width(), rect.height()], spacing)
+            .with_seed(rng.gen())
+            .iter()
+            .map(|point| [rect.min().x + point[0], rect.min().y + point[1]])
+            .filter(|&point| contains(polygon, point))
+            .collect();
+        shuffle_and_truncate(points, limit, rng)
+    }
+}
+
+/// Complete spatial randomness, points placed independently of each other
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct CompleteSpatialRandomness;
+
+impl Sampler for CompleteSpatialRandomness {
+    fn sample(&self, polygon: &Polygon, _spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        (0..limit * ATTEMPTS_PER_POINT)
+            .filter_map(|_| random_point_in_rect(polygon, rng))
+            .filter(|&point| contains(polygon, point))
+            .take(limit)
+            .collect()
+    }
+}
+
+/// Thomas process: cluster centres are placed at random and the trees around them
+/// at normally distributed offsets
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ThomasProcess {
+    /// Mean number of trees in a cluster
+    pub cluster_size: f64,
+    /// Standard deviation of the offsets as multiples of the spacing
+    pub spread: f64,
+}
+
+impl Default for ThomasProcess {
+    fn default() -> Self {
+        ThomasProcess { cluster_size: 8.0, spread: 2.0 }
+    }
+}
+
+/// Matérn cluster process: like the Thomas process, but the trees are spread uniformly
+/// in a disc around the cluster centre
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MaternProcess {
+    /// Mean number of trees in a cluster
+    pub cluster_size: f64,
+    /// Radius of a cluster as multiples of the spacing
+    pub cluster_radius: f64,
+}
+
+impl Default for MaternProcess {
+    fn default() -> Self {
+        MaternProcess { cluster_size: 8.0, cluster_radius: 3.0 }
+    }
+}
+
+// Places clusters until `limit` points are inside the polygon. `offset` gives a point relative to its cluster centre.
+fn sample_clusters<F>(polygon: &Polygon, limit: usize, cluster_size: f64, rng: &mut dyn RngCore, mut offset: F) -> Vec<[f64; 2]>
+where
+    F: FnMut(&mut dyn RngCore) -> [f64; 2],
+{
+    let Ok(children) = Poisson::new(cluster_size.max(f64::MIN_POSITIVE)) else {
+        return vec![];
+    };
+    let mut points = Vec::with_capacity(limit);
+
+    for _ in 0..limit * ATTEMPTS_PER_POINT {
+        if points.len() >= limit {
+            break;
+        }
+        let Some(centre) = random_point_in_rect(polygon, rng) else {
+            break;
+        };
+        let count: f64 = children.sample(rng);
+
+        for _ in 0..count as usize {
+            let [dx, dy] = offset(rng);
+            let point = [centre[0] + dx, centre[1] + dy];
+            if points.len() < limit && contains(polygon, point) {
+                points.push(point);
+            }
+        }
+    }
+
+    points
+}
+
+impl Sampler for ThomasProcess {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        let Ok(normal) = Normal::new(0.0, self.spread * spacing) else {
+            return vec![];
+        };
+        sample_clusters(polygon, limit, self.cluster_size, rng, |rng| [normal.sample(rng), normal.sample(rng)])
+    }
+}
+
+impl Sampler for MaternProcess {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        let radius = self.cluster_radius * spacing;
+        sample_clusters(polygon, limit, self.cluster_size, rng, |rng| {
+            // Square root of the distance for a uniform density in the disc
+            let distance = radius * rng.gen::<f64>().sqrt();
+            let angle = rng.gen_range(0.0..2.0 * PI);
+            [distance * angle.cos(), distance * angle.sin()]
+        })
+    }
+}
+
+// Accepted points by grid cell, so that only the neighbouring cells are checked for close points
+pub(crate) struct PointIndex {
+    min_distance: f64,
+    cell_size: f64,
+    // Units of the minimum distance per unit of the point coordinates along x and y
+    scale: [f64; 2],
+    cells: HashMap<(i64, i64), Vec<[f64; 2]>>,
+}
+
+impl PointIndex {
+    pub(crate) fn new(min_distance: f64) -> Self {
+        PointIndex::with_scale(min_distance, [1.0, 1.0])
+    }
+
+    // Index of points whose coordinates are scaled by `scale` before measuring distances,
+    // e.g. to metres from degrees of longitude and latitude
+    pub(crate) fn with_scale(min_distance: f64, scale: [f64; 2]) -> Self {
+        PointIndex { min_distance, cell_size: min_distance.max(f64::MIN_POSITIVE), scale, cells: HashMap::new() }
+    }
+
+    fn cell(&self, point: [f64; 2]) -> (i64, i64) {
+        ((point[0] / self.cell_size).floor() as i64, (point[1] / self.cell_size).floor() as i64)
+    }
+
+    // Adds the point if no earlier point is closer than the minimum distance
+    pub(crate) fn try_insert(&mut self, point: [f64; 2]) -> bool {
+        let point = [point[0] * self.scale[0], point[1] * self.scale[1]];
+        let (cx, cy) = self.cell(point);
+        let inhibited = (cx - 1..=cx + 1)
+            .flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y)))
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .any(|other| (other[0] - point[0]).hypot(other[1] - point[1]) < self.min_distance);
+
+        if !inhibited {
+            self.cells.entry((cx, cy)).or_default().push(point);
+        }
+        !inhibited
+    }
+}
+
+/// Simple sequential inhibition: random points are accepted if no earlier point is closer than
+/// the inhibition distance, which gives more regular spacing than complete randomness
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RegularInhibition {
+    /// Minimum distance between trees as multiples of the spacing
+    pub distance: f64,
+}
+
+impl Default for RegularInhibition {
+    fn default() -> Self {
+        RegularInhibition { distance: 0.8 }
+    }
+}
+
+impl Sampler for RegularInhibition {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        let mut index = PointIndex::new(self.distance * spacing);
+        let mut points = Vec::with_capacity(limit);
+
+        for _ in 0..limit * ATTEMPTS_PER_POINT {
+            if points.len() >= limit {
+                break;
+            }
+            let Some(point) = random_point_in_rect(polygon, rng) else {
+                break;
+            };
+            if contains(polygon, point) && index.try_insert(point) {
+                points.push(point);
+            }
+        }
+
+        points
+    }
+}
+
+/// Sampler chosen in the generation options
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SamplingMethod {
+    JitteredGrid(JitteredGrid),
+    PoissonDisk,
+    Random,
+    Thomas(ThomasProcess),
+    Matern(MaternProcess),
+    Inhibition(RegularInhibition),
+}
+
+impl Default for SamplingMethod {
+    fn default() -> Self {
+        SamplingMethod::JitteredGrid(JitteredGrid::default())
+    }
+}
+
+impl Sampler for SamplingMethod {
+    fn sample(&self, polygon: &Polygon, spacing: f64, limit: usize, rng: &mut dyn RngCore) -> Vec<[f64; 2]> {
+        match self {
+            SamplingMethod::JitteredGrid(sampler) => sampler.sample(polygon, spacing, limit, rng),
+            SamplingMethod::PoissonDisk => PoissonDisk.sample(polygon, spacing, limit, rng),
+            SamplingMethod::Random => CompleteSpatialRandomness.sample(polygon, spacing, limit, rng),
+            SamplingMethod::Thomas(sampler) => sampler.sample(polygon, spacing, limit, rng),
+            SamplingMethod::Matern(sampler) => sampler.sample(polygon, spacing, limit, rng),
+            SamplingMethod::Inhibition(sampler) => sampler.sample(polygon, spacing, limit, rng),
+        }
+    }
+}
+
+/// Sampling methods by stand and species. A species method overrides the stand's method,
+/// so e.g. birch can be clumped within an otherwise regular pine stand.
+/// Species are keyed by their code, e.g. `{"species": {"3": {"method": "thomas", "cluster_size": 6.0, "spread": 1.5}}}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SamplingOptions {
+    pub default: SamplingMethod,
+    /// By stand id
+    pub stands: HashMap<String, SamplingMethod>,
+    pub species: HashMap<TreeSpecies, SamplingMethod>,
+    /// Minimum distance in metres between the trees of all strata of the same storey.
+    /// Trees of different storeys, e.g. undergrowth beneath the dominant storey, can be closer.
+    /// None samples every stratum independently.
+    pub joint_min_distance: Option<f64>,
+}
+
+impl SamplingOptions {
+    pub fn method(&self, stand_id: &str, species: TreeSpecies) -> &SamplingMethod {
+        self.species.get(&species)
+            .or_else(|| self.stands.get(stand_id))
+            .unwrap_or(&self.default)
+    }
+}
+
+// Spacing is multiplied by this after a pass that gave too few points
+const SPACING_SHRINK: f64 = 0.8;
+const MAX_FILL_PASSES: usize = 6;
+
+/// How filling one stratum went
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct FillStats {
+    pub target: usize,
+    pub achieved: usize,
+    /// Sampler passes, each with a smaller spacing than the one before
+    pub passes: usize,
+    /// Spacing of the last pass
+    pub spacing: f64,
+    /// Whether points were added at random after the passes
+    pub fallback: bool,
+}
+
+impl FillStats {
+    pub fn is_complete(&self) -> bool {
+        self.achieved >= self.target
+    }
+
+    // Adds the fill of the same stratum in another part of a stand
+    fn add(&mut self, other: &FillStats) {
+        self.target += other.target;
+        self.achieved += other.achieved;
+        self.passes = self.passes.max(other.passes);
+        self.spacing = self.spacing.min(other.spacing);
+        self.fallback |= other.fallback;
+    }
+}
+
+/// Samples `target` points. If the sampler gives too few, e.g. a hex grid in a narrow polygon,
+/// it's run again with a smaller spacing, and the points still missing after the last pass
+/// are placed at random.
+pub fn fill_to_target(
+    sampler: &dyn Sampler,
+    polygon: &Polygon,
+    spacing: f64,
+    target: usize,
+    rng: &mut dyn RngCore
+) -> (Vec<[f64; 2]>, FillStats) {
+    let mut stats = FillStats { target, spacing, ..Default::default() };
+    let mut points = Vec::new();
+
+    while stats.passes < MAX_FILL_PASSES && points.len() < target {
+        stats.spacing = spacing * SPACING_SHRINK.powi(stats.passes as i32);
+        let sampled = sampler.sample(polygon, stats.spacing, target, rng);
+        if sampled.len() > points.len() {
+            points = sampled;
+        }
+        stats.passes += 1;
+    }
+
+    if points.len() < target {
+        stats.fallback = true;
+        points.extend(CompleteSpatialRandomness.sample(polygon, stats.spacing, target - points.len(), rng));
+    }
+
+    stats.achieved = points.len();
+    (points, stats)
+}
+
+/// Fill of one stratum of a stand
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct StratumFill {
+    /// Index of the stratum in its strata
+    pub index: usize,
+    pub tree_species: TreeSpecies,
+    pub storey: Storey,
+    #[serde(flatten)]
+    pub stats: FillStats,
+}
+
+/// Target and achieved tree counts of the strata of a stand
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FillReport {
+    pub strata: Vec<StratumFill>,
+}
+
+impl FillReport {
+    pub fn is_complete(&self) -> bool {
+        self.strata.iter().all(|stratum| stratum.stats.is_complete())
+    }
+
+    pub fn target(&self) -> usize {
+        self.strata.iter().map(|stratum| stratum.stats.target).sum()
+    }
+
+    pub fn achieved(&self) -> usize {
+        self.strata.iter().map(|stratum| stratum.stats.achieved).sum()
+    }
+
+    // Strata that got fewer trees than their target
+    pub fn incomplete(&self) -> impl Iterator<Item = &StratumFill> {
+        self.strata.iter().filter(|stratum| !stratum.stats.is_complete())
+    }
+
+    // Adds the report of another part of the same stand, strata with the same index are summed
+    pub fn merge(&mut self, other: FillReport) {
+        for stratum in other.strata {
+            match self.strata.iter_mut().find(|existing| existing.index == stratum.index) {
+                Some(existing) => existing.stats.add(&stratum.stats),
+                None => self.strata.push(stratum),
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[test]
+fn test_samplers() {
+    use geo::LineString;
+    use rand::SeedableRng;
+
+    let polygon = Polygon::new(
+        LineString::from(vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (0.0, 0.0)]),
+        vec![],
+    );
+    let min_distance = |points: &[[f64; 2]]| -> f64 {
+        points.iter().enumerate()
+            .flat_map(|(i, a)| points[i + 1..].iter().map(move |b| (a[0] - b[0]).hypot(a[1] - b[1])))
+            .fold(f64::INFINITY, f64::min)
+    };
+    let methods = [
+        SamplingMethod::default(),
+        SamplingMethod::PoissonDisk,
+        SamplingMethod::Random,
+        SamplingMethod::Thomas(ThomasProcess::default()),
+        SamplingMethod::Matern(MaternProcess::default()),
+        SamplingMethod::Inhibition(RegularInhibition::default()),
+    ];
+
+    for method in methods {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let points = method.sample(&polygon, 4.0, 200, &mut rng);
+        assert_eq!(points.len(), 200, "{:?}", method);
+        assert!(points.iter().all(|&point| contains(&polygon, point)));
+
+        match method {
+            SamplingMethod::PoissonDisk => assert!(min_distance(&points) >= 4.0),
+            SamplingMethod::Inhibition(inhibition) => assert!(min_distance(&points) >= inhibition.distance * 4.0),
+            _ => {}
+        }
+    }
+
+    let options = SamplingOptions {
+        species: HashMap::from([(TreeSpecies::SilverBirch, SamplingMethod::Thomas(ThomasProcess::default()))]),
+        ..Default::default()
+    };
+    assert_eq!(options.method("1", TreeSpecies::ScotsPine), &SamplingMethod::default());
+    assert!(matches!(options.method("1", TreeSpecies::SilverBirch), SamplingMethod::Thomas(_)));
+}
+
+#[test]
+fn test_sampling_options_from_json() {
+    let json = r#"{
+        "default": {"method": "poisson_disk"},
+        "stands": {"2553941": {"method": "jittered_grid", "jitter": 0.5}},
+        "species": {"3": {"method": "thomas", "cluster_size": 6.0, "spread": 1.5}},
+        "joint_min_distance": 1.5
+    }"#;
+    let options: SamplingOptions = serde_json::from_str(json).unwrap();
+
+    assert_eq!(options.method("1", TreeSpecies::ScotsPine), &SamplingMethod::PoissonDisk);
+    assert_eq!(options.method("2553941", TreeSpecies::ScotsPine), &SamplingMethod::JitteredGrid(JitteredGrid { jitter: 0.5 }));
+    assert_eq!(
+        options.method("2553941", TreeSpecies::from_code(3)),
+        &SamplingMethod::Thomas(ThomasProcess { cluster_size: 6.0, spread: 1.5 })
+    );
+    assert_eq!(options.joint_min_distance, Some(1.5));
+
+    // Missing fields keep their defaults
+    assert_eq!(serde_json::from_str::<SamplingOptions>("{}").unwrap(), SamplingOptions::default());
+    let round_tripped: SamplingOptions = serde_json::from_str(&serde_json::to_string(&options).unwrap()).unwrap();
+    assert_eq!(round_tripped, options);
+}
+
+#[test]
+fn test_fill_to_target() {
+    use geo::LineString;
+    use rand::SeedableRng;
+
+    // A narrow strip where a hex grid with this spacing has room for only a few points
+    let polygon = Polygon::new(
+        LineString::from(vec![(0.0, 0.0), (100.0, 0.0), (100.0, 3.0), (0.0, 3.0), (0.0, 0.0)]),
+        vec![],
+    );
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    assert!(JitteredGrid::default().sample(&polygon, 4.0, 150, &mut rng).len() < 150);
+
+    let (points, stats) = fill_to_target(&JitteredGrid::default(), &polygon, 4.0, 150, &mut rng);
+    assert_eq!(points.len(), 150);
+    assert!(stats.is_complete() && stats.passes > 1 && stats.spacing < 4.0);
+    assert!(points.iter().all(|&point| contains(&polygon, point)));
+
+    let (points, stats) = fill_to_target(&PoissonDisk, &polygon, 4.0, 10, &mut rng);
+    assert_eq!((points.len(), stats.passes, stats.fallback), (10, 1, false));
+}
@@ -1,5 +1,10 @@
 use wasm_bindgen::prelude::*;
 use web_sys::console::log_1;
+use crate::forest_property::tree::Tree;
+
+/// Values per tree in the buffer: x, y, species, diameter, height,
+/// crown radius, crown base height and crown shape
+pub const VALUES_PER_TREE: usize = 8;
 
 #[wasm_bindgen]
 pub struct SharedBuffer {
@@ -11,8 +16,8 @@ pub struct SharedBuffer {
 impl SharedBuffer {
     #[wasm_bindgen(constructor)]
     pub fn new(num_trees: usize) -> SharedBuffer {
-        // Each tree will have 3 values: x, y (f64), and species (u8 stored as f64)
-        let size = num_trees * 3;
+        // Each tree has VALUES_PER_TREE values, codes are stored as f64 too
+        let size = num_trees * VALUES_PER_TREE;
         let buffer = vec![0f64; size].into_boxed_slice(); // Allocate memory
         let ptr = buffer.as_ptr() as *mut f64; // Get raw pointer to the buffer
         let len = buffer.len(); // Length of the buffer
@@ -28,15 +33,38 @@ impl SharedBuffer {
         self.len
     }
 
-    /// Fills the buffer with data for a single tree (x, y, species)
+    pub fn values_per_tree(&self) -> usize {
+        VALUES_PER_TREE
+    }
+
+    // Number of trees that fit in the buffer
+    pub fn capacity(&self) -> usize {
+        self.len / VALUES_PER_TREE
+    }
+}
+
+impl SharedBuffer {
+    /// Fills the buffer with data for a single tree.
     /// `index` is the index of the tree in the buffer (0-based)
-    pub fn fill_tree(&self, index: usize, x: f64, y: f64, species: u8) {
-        let base = index * 3; // 3 values per tree: x, y, species
-        if base + 2 < self.len / 3 && species != 0 {
-            unsafe {
-                *self.ptr.add(base) = x;           // x coordinate
-                *self.ptr.add(base + 1) = y;       // y coordinate
-                *self.ptr.add(base + 2) = species as f64; // species as u8 stored in f64
+    pub fn fill_tree(&self, index: usize, tree: &Tree) {
+        let base = index * VALUES_PER_TREE;
+        let species = tree.species().code();
+        if base + VALUES_PER_TREE <= self.len && species != 0 {
+            let crown = tree.crown();
+            let values = [
+                tree.position().0,
+                tree.position().1,
+                species as f64,
+                tree.diameter() as f64,
+                tree.height() as f64,
+                crown.radius as f64,
+                crown.base_height as f64,
+                crown.shape.code() as f64,
+            ];
+            for (i, value) in values.into_iter().enumerate() {
+                unsafe {
+                    *self.ptr.add(base + i) = value;
+                }
             }
         }
     }